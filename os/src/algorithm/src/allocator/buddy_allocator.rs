@@ -0,0 +1,219 @@
+//! 提供伙伴系统实现的分配器 [`BuddyAllocator`]
+
+use super::{Allocator, ContiguousAllocator};
+use alloc::{vec, vec::Vec};
+use core::mem::size_of;
+
+/// 位图中每个字的位数
+const WORD_BITS: usize = 64;
+
+/// 使用伙伴系统实现分配器
+///
+/// 空闲空间被划分为大小为 2^k、起始序号对齐到 2^k 的块，第 k 个位图记录所有空闲的 2^k 大小的块。
+/// 分配时将较大的块逐级对半拆分；回收时如果相邻的「伙伴」块也空闲，则合并为更大的块。
+/// 连续分配的长度不必是 2 的幂，多出的尾部会立即回收。
+///
+/// 位图在创建时一次分配，共约 2 × 容量 位，之后分配和回收都不再使用堆
+pub struct BuddyAllocator {
+    /// 第 k 项为 2^k 大小的块的位图，第 i 位表示从 i × 2^k 开始的块是否空闲
+    free: Vec<Vec<u64>>,
+    /// 第 k 项为空闲的 2^k 大小的块的数量
+    counts: Vec<usize>,
+    /// 第 k 项为第 k 个位图中可能不为 0 的第一个字，之前的字都为 0
+    hints: Vec<usize>,
+}
+
+impl Allocator for BuddyAllocator {
+    fn new(capacity: usize) -> Self {
+        let orders = log2_floor(capacity.max(1)) + 1;
+        let mut allocator = Self {
+            free: (0..orders)
+                .map(|order| {
+                    let blocks = (capacity + (1 << order) - 1) >> order;
+                    vec![0; (blocks + WORD_BITS - 1) / WORD_BITS]
+                })
+                .collect(),
+            counts: vec![0; orders],
+            hints: vec![0; orders],
+        };
+        allocator.insert_range(0, capacity);
+        allocator
+    }
+
+    fn alloc(&mut self) -> Option<usize> {
+        self.alloc_contiguous(1, 1)
+    }
+
+    fn dealloc(&mut self, index: usize) {
+        self.dealloc_contiguous(index, 1);
+    }
+}
+
+impl ContiguousAllocator for BuddyAllocator {
+    fn alloc_contiguous(&mut self, count: usize, align: usize) -> Option<usize> {
+        assert!(count > 0 && align.is_power_of_two());
+        // 块的起始序号对齐到块的大小，因此只要块足够大，就满足对齐的要求
+        let order = log2_ceil(count).max(log2_floor(align));
+        let found = (order..self.free.len()).find(|&k| self.counts[k] > 0)?;
+        let start = self.take(found);
+        // 逐级拆分，后一半放回对应的位图
+        for k in (order..found).rev() {
+            self.set(k, start + (1 << k));
+        }
+        self.dealloc_contiguous(start + count, (1 << order) - count);
+        Some(start)
+    }
+
+    fn dealloc_contiguous(&mut self, start: usize, count: usize) {
+        let mut index = start;
+        while index < start + count {
+            let order = largest_block(index, start + count, self.free.len() - 1);
+            self.insert_block(index, order);
+            index += 1 << order;
+        }
+    }
+
+    fn reserve(&mut self, start: usize, count: usize) {
+        if count == 0 {
+            return;
+        }
+        let end = start + count;
+        for order in 0..self.free.len() {
+            // 只检查与区间重叠的块
+            for block in (start >> order)..=((end - 1) >> order) {
+                let block = block << order;
+                if self.clear(order, block) {
+                    // 块在区间之外的部分仍然空闲，它们来自同一个块，不需要合并
+                    self.insert_range(block, start.max(block));
+                    self.insert_range(end.min(block + (1 << order)), block + (1 << order));
+                }
+            }
+        }
+    }
+}
+
+impl BuddyAllocator {
+    /// 将 `[start, end)` 划分为尽量大的块加入空闲位图，不尝试合并
+    fn insert_range(&mut self, start: usize, end: usize) {
+        let mut index = start;
+        while index < end {
+            let order = largest_block(index, end, self.free.len() - 1);
+            self.set(order, index);
+            index += 1 << order;
+        }
+    }
+
+    /// 回收一个 2^order 大小的块，并尽可能与伙伴合并
+    fn insert_block(&mut self, mut index: usize, mut order: usize) {
+        while order + 1 < self.free.len() {
+            let buddy = index ^ (1 << order);
+            if !self.clear(order, buddy) {
+                break;
+            }
+            index = index.min(buddy);
+            order += 1;
+        }
+        assert!(self.set(order, index), "double free");
+    }
+
+    /// 将从 `start` 开始的 2^order 大小的块标记为空闲，已经空闲则返回 `false`
+    fn set(&mut self, order: usize, start: usize) -> bool {
+        let (word, bit) = position(order, start);
+        if self.free[order][word] & bit != 0 {
+            return false;
+        }
+        self.free[order][word] |= bit;
+        self.counts[order] += 1;
+        self.hints[order] = self.hints[order].min(word);
+        true
+    }
+
+    /// 如果从 `start` 开始的 2^order 大小的块空闲，则将其取出并返回 `true`
+    fn clear(&mut self, order: usize, start: usize) -> bool {
+        let (word, bit) = position(order, start);
+        match self.free[order].get_mut(word) {
+            Some(value) if *value & bit != 0 => {
+                *value &= !bit;
+                self.counts[order] -= 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// 取出任意一个 2^order 大小的空闲块，调用前需要确认这样的块存在
+    fn take(&mut self, order: usize) -> usize {
+        let bitmap = &mut self.free[order];
+        let word = (self.hints[order]..bitmap.len())
+            .find(|&word| bitmap[word] != 0)
+            .unwrap();
+        let bit = bitmap[word].trailing_zeros() as usize;
+        bitmap[word] &= !(1 << bit);
+        self.counts[order] -= 1;
+        self.hints[order] = word;
+        (word * WORD_BITS + bit) << order
+    }
+}
+
+/// 从 `start` 开始的 2^order 大小的块在位图中的字序号和位
+fn position(order: usize, start: usize) -> (usize, u64) {
+    let block = start >> order;
+    (block / WORD_BITS, 1 << (block % WORD_BITS))
+}
+
+/// 从 `index` 开始、不超过 `end` 的最大的对齐块的阶数，不超过 `max_order`
+fn largest_block(index: usize, end: usize, max_order: usize) -> usize {
+    let mut order = (index.trailing_zeros() as usize).min(max_order);
+    while index + (1 << order) > end {
+        order -= 1;
+    }
+    order
+}
+
+/// 向下取整的 log2
+fn log2_floor(value: usize) -> usize {
+    size_of::<usize>() * 8 - 1 - value.leading_zeros() as usize
+}
+
+/// 向上取整的 log2
+fn log2_ceil(value: usize) -> usize {
+    log2_floor(value.next_power_of_two())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_and_merge() {
+        let mut allocator = BuddyAllocator::new(16);
+        assert_eq!(allocator.counts, [0, 0, 0, 0, 1]);
+        // 分配一个元素时 16 被逐级拆分为 8、4、2、1
+        assert_eq!(allocator.alloc(), Some(0));
+        assert_eq!(allocator.counts, [1, 1, 1, 1, 0]);
+        assert_eq!(allocator.alloc(), Some(1));
+        // 长度 3 的分配占用一个 4 的块，尾部立即回收
+        assert_eq!(allocator.alloc_contiguous(3, 1), Some(4));
+        assert_eq!(allocator.counts, [1, 1, 0, 1, 0]);
+        // 全部回收之后重新合并为一个 16 的块
+        allocator.dealloc(0);
+        allocator.dealloc(1);
+        allocator.dealloc_contiguous(4, 3);
+        assert_eq!(allocator.counts, [0, 0, 0, 0, 1]);
+    }
+
+    #[test]
+    fn alignment_and_reserve() {
+        let mut allocator = BuddyAllocator::new(40);
+        allocator.reserve(0, 3);
+        assert_eq!(allocator.alloc_contiguous(4, 8), Some(8));
+        assert_eq!(allocator.alloc_contiguous(16, 1), Some(16));
+        // 剩下 3、4..8、12..16、32..40
+        assert_eq!(allocator.alloc_contiguous(8, 8), Some(32));
+        assert_eq!(allocator.alloc_contiguous(8, 1), None);
+        let mut singles: Vec<usize> = (0..9).filter_map(|_| allocator.alloc()).collect();
+        singles.sort_unstable();
+        assert_eq!(singles, [3, 4, 5, 6, 7, 12, 13, 14, 15]);
+        assert_eq!(allocator.alloc(), None);
+    }
+}
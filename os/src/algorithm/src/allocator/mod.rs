@@ -1,5 +1,6 @@
 //! 负责分配 / 回收的数据结构
 
+mod buddy_allocator;
 mod segment_tree_allocator;
 mod stacked_allocator;
 
@@ -13,8 +14,22 @@ pub trait Allocator {
     fn dealloc(&mut self, index: usize);
 }
 
+/// 可以分配连续区间的分配器
+pub trait ContiguousAllocator: Allocator {
+    /// 分配连续的 `count` 个元素，起始序号是 `align` 的倍数（`align` 为 2 的幂），无法分配则返回 `None`
+    fn alloc_contiguous(&mut self, count: usize, align: usize) -> Option<usize>;
+    /// 回收从 `start` 开始的连续 `count` 个元素
+    fn dealloc_contiguous(&mut self, start: usize, count: usize);
+    /// 将从 `start` 开始的连续 `count` 个元素标记为已分配，它们不会被分配出去
+    fn reserve(&mut self, start: usize, count: usize);
+}
+
+pub use buddy_allocator::BuddyAllocator;
 pub use segment_tree_allocator::SegmentTreeAllocator;
 pub use stacked_allocator::StackedAllocator;
 
 /// 默认使用的分配器
 pub type AllocatorImpl = StackedAllocator;
+
+/// 默认使用的连续分配器
+pub type ContiguousAllocatorImpl = BuddyAllocator;
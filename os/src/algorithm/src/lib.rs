@@ -7,7 +7,9 @@
 extern crate alloc;
 
 mod allocator;
+mod replacer;
 mod scheduler;
 
 pub use allocator::*;
+pub use replacer::*;
 pub use scheduler::*;
@@ -0,0 +1,53 @@
+//! 老化算法的页面置换器 [`AgingReplacer`]
+
+use super::Replacer;
+use alloc::vec::Vec;
+
+/// 将页面和老化计数打包
+struct AgingPage<PageType: Copy + Eq> {
+    /// 页面
+    page: PageType,
+    /// 老化计数，最高位表示最近一次查询时是否被访问过
+    counter: u8,
+}
+
+/// 采用老化算法（近似 LRU）的页面置换器
+///
+/// 每次选择时，所有页面的计数右移一位，并将访问标记放入最高位。
+/// 计数最小的页面即为最近最少使用的页面，将其换出
+pub struct AgingReplacer<PageType: Copy + Eq> {
+    pages: Vec<AgingPage<PageType>>,
+}
+
+/// `Default` 创建一个空的置换器
+impl<PageType: Copy + Eq> Default for AgingReplacer<PageType> {
+    fn default() -> Self {
+        Self { pages: Vec::new() }
+    }
+}
+
+impl<PageType: Copy + Eq> Replacer<PageType> for AgingReplacer<PageType> {
+    fn add_page(&mut self, page: PageType) {
+        // 新加入的页面视为刚刚被访问过
+        self.pages.push(AgingPage {
+            page,
+            counter: 1 << 7,
+        });
+    }
+    fn remove_page(&mut self, page: &PageType) {
+        self.pages.retain(|p| p.page != *page);
+    }
+    fn choose_victim(&mut self, mut accessed: impl FnMut(&PageType) -> bool) -> Option<PageType> {
+        // 更新所有页面的计数
+        for page in self.pages.iter_mut() {
+            page.counter = (page.counter >> 1) | ((accessed(&page.page) as u8) << 7);
+        }
+        // 换出计数最小者（相同时取最早加入的）
+        let (index, _) = self
+            .pages
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, page)| page.counter)?;
+        Some(self.pages.remove(index).page)
+    }
+}
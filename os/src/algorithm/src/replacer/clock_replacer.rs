@@ -0,0 +1,62 @@
+//! 时钟算法的页面置换器 [`ClockReplacer`]
+
+use super::Replacer;
+use alloc::vec::Vec;
+
+/// 采用时钟（二次机会）算法的页面置换器
+///
+/// 所有页面排成一个环，指针依次扫过。被访问过的页面会清除访问标记并获得第二次机会，
+/// 遇到的第一个未被访问的页面被换出
+pub struct ClockReplacer<PageType: Copy + Eq> {
+    /// 环形排列的页面
+    pages: Vec<PageType>,
+    /// 时钟指针的位置
+    hand: usize,
+}
+
+/// `Default` 创建一个空的置换器
+impl<PageType: Copy + Eq> Default for ClockReplacer<PageType> {
+    fn default() -> Self {
+        Self {
+            pages: Vec::new(),
+            hand: 0,
+        }
+    }
+}
+
+impl<PageType: Copy + Eq> Replacer<PageType> for ClockReplacer<PageType> {
+    fn add_page(&mut self, page: PageType) {
+        // 插入到指针之前，即最后才会被扫到的位置
+        self.pages.insert(self.hand, page);
+        self.hand = (self.hand + 1) % self.pages.len();
+    }
+    fn remove_page(&mut self, page: &PageType) {
+        if let Some(index) = self.pages.iter().position(|p| p == page) {
+            self.pages.remove(index);
+            if index < self.hand {
+                self.hand -= 1;
+            }
+            if self.hand >= self.pages.len() {
+                self.hand = 0;
+            }
+        }
+    }
+    fn choose_victim(&mut self, mut accessed: impl FnMut(&PageType) -> bool) -> Option<PageType> {
+        if self.pages.is_empty() {
+            return None;
+        }
+        // 最多扫过两圈：第一圈清除所有访问标记，第二圈一定能找到页面。
+        // 如果页面在扫描过程中不断被标记为访问过，则直接换出指针所指的页面
+        for _ in 0..2 * self.pages.len() {
+            if !accessed(&self.pages[self.hand]) {
+                break;
+            }
+            self.hand = (self.hand + 1) % self.pages.len();
+        }
+        let victim = self.pages.remove(self.hand);
+        if self.hand >= self.pages.len() {
+            self.hand = 0;
+        }
+        Some(victim)
+    }
+}
@@ -0,0 +1,32 @@
+//! 先入先出的页面置换算法 [`FifoReplacer`]
+
+use super::Replacer;
+use alloc::collections::VecDeque;
+
+/// 采用 FIFO 算法的页面置换器，换出最早进入内存的页面
+pub struct FifoReplacer<PageType: Copy + Eq> {
+    queue: VecDeque<PageType>,
+}
+
+/// `Default` 创建一个空的置换器
+impl<PageType: Copy + Eq> Default for FifoReplacer<PageType> {
+    fn default() -> Self {
+        Self {
+            queue: VecDeque::new(),
+        }
+    }
+}
+
+impl<PageType: Copy + Eq> Replacer<PageType> for FifoReplacer<PageType> {
+    fn add_page(&mut self, page: PageType) {
+        // 加入队列尾部
+        self.queue.push_back(page);
+    }
+    fn remove_page(&mut self, page: &PageType) {
+        self.queue.retain(|p| p != page);
+    }
+    fn choose_victim(&mut self, _accessed: impl FnMut(&PageType) -> bool) -> Option<PageType> {
+        // 不考虑访问情况，直接取出队首
+        self.queue.pop_front()
+    }
+}
@@ -0,0 +1,30 @@
+//! 页面置换算法
+
+mod aging_replacer;
+mod clock_replacer;
+mod fifo_replacer;
+
+/// 页面置换器
+///
+/// `PageType` 用于标识页面，例如虚拟页号
+///
+/// ### 使用方法
+/// - 页面分配物理帧后，调用 [`Replacer::add_page()`] 来记录它。
+/// - 页面被释放时，调用 [`Replacer::remove_page()`] 来将其移除。
+/// - 需要腾出物理帧时，调用 [`Replacer::choose_victim()`] 选出一个页面换出，选出的页面会被移除。
+///   其中 `accessed` 用来查询一个页面从上次查询至今是否被访问过（并清除访问标记），
+///   不同的算法会以不同的方式利用这一信息。
+pub trait Replacer<PageType: Copy + Eq>: Default {
+    /// 记录一个驻留在内存中的页面
+    fn add_page(&mut self, page: PageType);
+    /// 移除一个页面
+    fn remove_page(&mut self, page: &PageType);
+    /// 选出一个页面换出，没有页面时返回 `None`
+    fn choose_victim(&mut self, accessed: impl FnMut(&PageType) -> bool) -> Option<PageType>;
+}
+
+pub use aging_replacer::AgingReplacer;
+pub use clock_replacer::ClockReplacer;
+pub use fifo_replacer::FifoReplacer;
+
+pub type ReplacerImpl<T> = ClockReplacer<T>;
@@ -23,7 +23,7 @@ impl<ThreadType: Clone + Eq> Scheduler<ThreadType> for FifoScheduler<ThreadType>
         // 加入链表尾部
         self.pool.push_back(thread);
     }
-    fn get_next(&mut self) -> Option<ThreadType> {
+    fn get_next(&mut self, _preempted: bool) -> Option<ThreadType> {
         // 从头部取出放回尾部，同时将其返回
         if let Some(thread) = self.pool.pop_front() {
             self.pool.push_back(thread.clone());
@@ -41,7 +41,7 @@ impl<ThreadType: Clone + Eq> Scheduler<ThreadType> for HrrnScheduler<ThreadType>
             thread,
         })
     }
-    fn get_next(&mut self) -> Option<ThreadType> {
+    fn get_next(&mut self, _preempted: bool) -> Option<ThreadType> {
         // 计时
         self.current_time += 1;
 
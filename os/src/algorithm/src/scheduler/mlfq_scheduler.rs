@@ -0,0 +1,173 @@
+//! 多级反馈队列的调度器 [`MlfqScheduler`]
+
+use super::Scheduler;
+use alloc::collections::LinkedList;
+use alloc::vec::Vec;
+
+/// 队列的层数，第 0 层优先级最高
+const LEVEL_COUNT: usize = 4;
+
+/// 每隔多少次调度，将所有线程提升回最高层
+const BOOST_INTERVAL: usize = 100;
+
+/// 第 `level` 层的时间片长度，单位为时钟中断次数
+fn time_slice(level: usize) -> usize {
+    1 << level
+}
+
+/// 将线程和调度信息打包
+struct MlfqThread<ThreadType: Clone + Eq> {
+    /// 在当前层已经用掉的时间片
+    used: usize,
+    /// 线程数据
+    pub thread: ThreadType,
+}
+
+/// 采用 MLFQ（多级反馈队列）的调度器
+///
+/// - 总是执行最高非空层中的第一个线程
+/// - 线程被时钟中断抢占时计入用掉的时间片，用完本层的整个时间片后降到下一层
+/// - 线程主动让出时留在本层，排到队尾
+/// - 每隔 [`BOOST_INTERVAL`] 次调度，所有线程回到最高层，避免低层线程饥饿
+///
+/// 交互式的线程经常休眠，很少用完时间片，因此会留在高层，得到及时的响应
+pub struct MlfqScheduler<ThreadType: Clone + Eq> {
+    /// 当前时间，单位为 `get_next()` 调用次数
+    current_time: usize,
+    /// 上一次返回的线程所在的层，线程仍在池中时一定位于该层队列的头部
+    running: Option<usize>,
+    /// 每一层的线程队列
+    queues: Vec<LinkedList<MlfqThread<ThreadType>>>,
+}
+
+/// `Default` 创建一个空的调度器
+impl<ThreadType: Clone + Eq> Default for MlfqScheduler<ThreadType> {
+    fn default() -> Self {
+        Self {
+            current_time: 0,
+            running: None,
+            queues: (0..LEVEL_COUNT).map(|_| LinkedList::new()).collect(),
+        }
+    }
+}
+
+impl<ThreadType: Clone + Eq> MlfqScheduler<ThreadType> {
+    /// 将所有线程提升回最高层，并清空用掉的时间片
+    fn boost(&mut self) {
+        let mut top = LinkedList::new();
+        for queue in self.queues.iter_mut() {
+            top.append(queue);
+        }
+        for thread in top.iter_mut() {
+            thread.used = 0;
+        }
+        self.queues[0] = top;
+    }
+}
+
+impl<ThreadType: Clone + Eq> Scheduler<ThreadType> for MlfqScheduler<ThreadType> {
+    /// MLFQ 根据线程的行为调整层级，设置的优先级被忽略
+    type Priority = usize;
+
+    fn add_thread(&mut self, thread: ThreadType) {
+        // 新线程进入最高层
+        self.queues[0].push_back(MlfqThread { used: 0, thread });
+    }
+    fn get_next(&mut self, preempted: bool) -> Option<ThreadType> {
+        // 计时
+        self.current_time += 1;
+
+        // 根据上一个线程的情况，决定它的去向
+        if let Some(level) = self.running.take() {
+            let mut thread = self.queues[level].pop_front().unwrap();
+            if preempted {
+                thread.used += 1;
+            }
+            if thread.used >= time_slice(level) {
+                // 用完了整个时间片，降到下一层
+                thread.used = 0;
+                self.queues[(level + 1).min(LEVEL_COUNT - 1)].push_back(thread);
+            } else if preempted {
+                // 时间片还没有用完，继续执行（除非更高层有线程）
+                self.queues[level].push_front(thread);
+            } else {
+                // 主动让出，排到本层队尾
+                self.queues[level].push_back(thread);
+            }
+        }
+
+        // 定期提升
+        if self.current_time % BOOST_INTERVAL == 0 {
+            self.boost();
+        }
+
+        // 返回最高非空层中的第一个线程
+        let level = (0..LEVEL_COUNT).find(|&level| !self.queues[level].is_empty())?;
+        self.running = Some(level);
+        Some(self.queues[level].front().unwrap().thread.clone())
+    }
+    fn remove_thread(&mut self, thread: &ThreadType) {
+        // 移除相应的线程并且确认恰移除一个线程
+        let mut count = 0;
+        for (level, queue) in self.queues.iter_mut().enumerate() {
+            if self.running == Some(level) && queue.front().map(|t| &t.thread) == Some(thread) {
+                self.running = None;
+            }
+            count += queue.drain_filter(|t| t.thread == *thread).count();
+        }
+        assert_eq!(count, 1);
+    }
+    fn set_priority(&mut self, _thread: ThreadType, _priority: usize) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 线程所在的层
+    fn level_of(scheduler: &MlfqScheduler<usize>, thread: usize) -> usize {
+        scheduler
+            .queues
+            .iter()
+            .position(|queue| queue.iter().any(|t| t.thread == thread))
+            .unwrap()
+    }
+
+    #[test]
+    fn demotion() {
+        let mut scheduler = MlfqScheduler::default();
+        scheduler.add_thread(1);
+        assert_eq!(scheduler.get_next(false), Some(1));
+        // 用完第 0 层的时间片后降到第 1 层
+        assert_eq!(scheduler.get_next(true), Some(1));
+        assert_eq!(level_of(&scheduler, 1), 1);
+        // 新线程进入第 0 层，优先执行
+        scheduler.add_thread(2);
+        assert_eq!(scheduler.get_next(true), Some(2));
+        // 主动让出的线程留在本层
+        assert_eq!(scheduler.get_next(false), Some(2));
+        assert_eq!(level_of(&scheduler, 2), 0);
+        // 第 1 层的时间片为 2，再被抢占一次后降到第 2 层
+        scheduler.remove_thread(&2);
+        assert_eq!(scheduler.get_next(true), Some(1));
+        assert_eq!(scheduler.get_next(true), Some(1));
+        assert_eq!(level_of(&scheduler, 1), 2);
+    }
+
+    #[test]
+    fn boost() {
+        let mut scheduler = MlfqScheduler::default();
+        scheduler.add_thread(1);
+        scheduler.add_thread(2);
+        while scheduler.current_time < BOOST_INTERVAL - 1 {
+            scheduler.get_next(true);
+        }
+        // 一直被抢占的线程降到最低层
+        assert_eq!(level_of(&scheduler, 1), LEVEL_COUNT - 1);
+        assert_eq!(level_of(&scheduler, 2), LEVEL_COUNT - 1);
+        // 提升之后都回到最高层
+        scheduler.get_next(true);
+        assert_eq!(level_of(&scheduler, 1), 0);
+        assert_eq!(level_of(&scheduler, 2), 0);
+    }
+}
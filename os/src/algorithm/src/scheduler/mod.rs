@@ -2,6 +2,8 @@
 
 mod fifo_scheduler;
 mod hrrn_scheduler;
+mod mlfq_scheduler;
+mod stride_scheduler;
 
 /// 线程调度器
 ///
@@ -9,7 +11,8 @@ mod hrrn_scheduler;
 ///
 /// ### 使用方法
 /// - 在每一个时间片结束后，调用 [`Scheduler::get_next()`] 来获取下一个时间片应当执行的线程。
-///   这个线程可能是上一个时间片所执行的线程。
+///   这个线程可能是上一个时间片所执行的线程。同时告知上一个线程是否因时间片用完而被抢占，
+///   以便调度器区分计算密集的线程和经常让出、休眠的交互式线程。
 /// - 当一个线程结束时，需要调用 [`Scheduler::remove_thread()`] 来将其移除。这个方法必须在
 ///   [`Scheduler::get_next()`] 之前调用。
 pub trait Scheduler<ThreadType: Clone + Eq>: Default {
@@ -18,7 +21,9 @@ pub trait Scheduler<ThreadType: Clone + Eq>: Default {
     /// 向线程池中添加一个线程
     fn add_thread(&mut self, thread: ThreadType);
     /// 获取下一个时间段应当执行的线程
-    fn get_next(&mut self) -> Option<ThreadType>;
+    ///
+    /// `preempted` 表示上一次返回的线程是否被时钟中断抢占（而不是主动让出、休眠或结束）
+    fn get_next(&mut self, preempted: bool) -> Option<ThreadType>;
     /// 移除一个线程
     fn remove_thread(&mut self, thread: &ThreadType);
     /// 设置线程的优先级
@@ -27,5 +32,13 @@ pub trait Scheduler<ThreadType: Clone + Eq>: Default {
 
 pub use fifo_scheduler::FifoScheduler;
 pub use hrrn_scheduler::HrrnScheduler;
+pub use mlfq_scheduler::MlfqScheduler;
+pub use stride_scheduler::StrideScheduler;
 
-pub type SchedulerImpl<T> = HrrnScheduler<T>;
+/// 默认使用的调度器：多级反馈队列，让交互式的线程及时得到响应
+#[cfg(not(feature = "stride"))]
+pub type SchedulerImpl<T> = MlfqScheduler<T>;
+
+/// 启用 `stride` 时使用的调度器：按照优先级分配时间片
+#[cfg(feature = "stride")]
+pub type SchedulerImpl<T> = StrideScheduler<T>;
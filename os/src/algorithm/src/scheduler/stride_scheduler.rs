@@ -0,0 +1,76 @@
+//! 步幅调度算法的调度器 [`StrideScheduler`]
+
+use super::Scheduler;
+use alloc::collections::LinkedList;
+
+/// 步幅的基数，线程的步幅为 `BIG_STRIDE / 优先级`
+const BIG_STRIDE: usize = 1 << 20;
+
+/// 将线程和调度信息打包
+struct StrideThread<ThreadType: Clone + Eq> {
+    /// 线程的优先级，越大则分到的时间片越多
+    priority: usize,
+    /// 累计的行程，每被分配一次时间片就增加一个步幅
+    pass: usize,
+    /// 线程数据
+    pub thread: ThreadType,
+}
+
+impl<ThreadType: Clone + Eq> StrideThread<ThreadType> {
+    /// 线程每次被调度时增加的行程
+    fn stride(&self) -> usize {
+        BIG_STRIDE / self.priority
+    }
+}
+
+/// 采用步幅调度（Stride Scheduling）的调度器
+///
+/// 每次选出行程最小的线程执行，并将其行程增加一个步幅。
+/// 步幅与优先级成反比，因此各线程分到的时间片与优先级成正比。
+pub struct StrideScheduler<ThreadType: Clone + Eq> {
+    /// 带有调度信息的线程池
+    pool: LinkedList<StrideThread<ThreadType>>,
+}
+
+/// `Default` 创建一个空的调度器
+impl<ThreadType: Clone + Eq> Default for StrideScheduler<ThreadType> {
+    fn default() -> Self {
+        Self {
+            pool: LinkedList::new(),
+        }
+    }
+}
+
+impl<ThreadType: Clone + Eq> Scheduler<ThreadType> for StrideScheduler<ThreadType> {
+    /// 优先级为正整数，为 0 时按 1 处理
+    type Priority = usize;
+
+    fn add_thread(&mut self, thread: ThreadType) {
+        // 新线程的行程取当前最小值，避免它因行程落后而长期独占
+        let pass = self.pool.iter().map(|t| t.pass).min().unwrap_or(0);
+        self.pool.push_back(StrideThread {
+            priority: 1,
+            pass,
+            thread,
+        })
+    }
+    fn get_next(&mut self, _preempted: bool) -> Option<ThreadType> {
+        // 遍历线程池，返回行程最小者
+        if let Some(best) = self.pool.iter_mut().min_by_key(|t| t.pass) {
+            best.pass += best.stride();
+            Some(best.thread.clone())
+        } else {
+            None
+        }
+    }
+    fn remove_thread(&mut self, thread: &ThreadType) {
+        // 移除相应的线程并且确认恰移除一个线程
+        let mut removed = self.pool.drain_filter(|t| t.thread == *thread);
+        assert!(removed.next().is_some() && removed.next().is_none());
+    }
+    fn set_priority(&mut self, thread: ThreadType, priority: usize) {
+        if let Some(t) = self.pool.iter_mut().find(|t| t.thread == thread) {
+            t.priority = priority.max(1);
+        }
+    }
+}
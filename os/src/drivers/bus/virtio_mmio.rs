@@ -4,9 +4,9 @@
 
 use super::super::block::virtio_blk;
 use crate::memory::{
-    frame::{FrameTracker, FRAME_ALLOCATOR},
+    frame::{FrameRangeTracker, FRAME_ALLOCATOR},
     mapping::Mapping,
-    PhysicalAddress, VirtualAddress, PAGE_SIZE,
+    PhysicalAddress, VirtualAddress,
 };
 use alloc::collections::btree_map::BTreeMap;
 use device_tree::{util::SliceRead, Node};
@@ -36,8 +36,8 @@ pub fn virtio_probe(node: &Node) {
 }
 
 lazy_static! {
-    /// 用于放置给设备 DMA 所用的物理页（[`FrameTracker`]）
-    pub static ref TRACKERS: RwLock<BTreeMap<PhysicalAddress, FrameTracker>> =
+    /// 用于放置给设备 DMA 所用的连续物理页（[`FrameRangeTracker`]），以起始物理地址为键
+    pub static ref TRACKERS: RwLock<BTreeMap<PhysicalAddress, FrameRangeTracker>> =
         RwLock::new(BTreeMap::new());
 }
 
@@ -45,31 +45,30 @@ lazy_static! {
 ///
 /// 为什么要求连续的物理内存？设备的 DMA 操作只涉及到内存和对应设备
 /// 这个过程不会涉及到 CPU 的 MMU 机制，我们只能给设备传递物理地址
-/// 而陷于我们之前每次只能分配一个物理页的设计，这里我们假设我们连续分配的地址是连续的
 #[no_mangle]
 extern "C" fn virtio_dma_alloc(pages: usize) -> PhysicalAddress {
-    let mut pa: PhysicalAddress = Default::default();
-    let mut last: PhysicalAddress = Default::default();
-    for i in 0..pages {
-        let tracker: FrameTracker = FRAME_ALLOCATOR.lock().alloc().unwrap();
-        if i == 0 {
-            pa = tracker.address();
-        } else {
-            assert_eq!(last + PAGE_SIZE, tracker.address());
-        }
-        last = tracker.address();
-        TRACKERS.write().insert(last, tracker);
-    }
+    let tracker = FRAME_ALLOCATOR
+        .lock()
+        .alloc_contiguous(pages, 1)
+        .expect("failed to allocate frames for DMA");
+    let pa = tracker.address();
+    TRACKERS.write().insert(pa, tracker);
     pa
 }
 
 /// 为 DMA 操作释放对应的之前申请的连续的物理页（为 [`virtio_drivers`] 库提供）
+///
+/// 地址或页数与申请时不符则返回 -1，不释放任何物理页
 #[no_mangle]
 extern "C" fn virtio_dma_dealloc(pa: PhysicalAddress, pages: usize) -> i32 {
-    for i in 0..pages {
-        TRACKERS.write().remove(&(pa + i * PAGE_SIZE));
+    let mut trackers = TRACKERS.write();
+    match trackers.get(&pa) {
+        Some(tracker) if tracker.len() == pages => {
+            trackers.remove(&pa);
+            0
+        }
+        _ => -1,
     }
-    0
 }
 
 /// 将物理地址转为虚拟地址（为 [`virtio_drivers`] 库提供）
@@ -85,7 +84,7 @@ extern "C" fn virtio_phys_to_virt(pa: PhysicalAddress) -> VirtualAddress {
 /// 将虚拟地址转为物理地址（为 [`virtio_drivers`] 库提供）
 ///
 /// 需要注意，实现这个函数的目的是告诉 DMA 具体的请求，请求在实现中会放在栈上面
-/// 而在我们的实现中，栈是以 Framed 的形式分配的，并不是高地址的线性映射 Linear
+/// 而在我们的实现中，栈是以 Lazy 的形式按需分配的，并不是高地址的线性映射 Linear
 /// 为了得到正确的物理地址并告诉 DMA 设备，我们只能查页表
 #[no_mangle]
 extern "C" fn virtio_virt_to_phys(va: VirtualAddress) -> PhysicalAddress {
@@ -9,6 +9,7 @@
     .section .text.entry
     .globl _start
 # 目前 _start 的功能：将预留的栈空间写入 $sp，然后跳转至 rust_main
+# 每个 hart 都从这里进入，a0 为 hart 编号
 _start:
     # 通过线性映射关系计算 boot_page_table 的物理页号
     lui t0, %hi(boot_page_table)
@@ -22,9 +23,15 @@ _start:
     csrw satp, t0
     sfence.vma
 
-    # 加载栈的虚拟地址
-    lui sp, %hi(boot_stack_top)
-    addi sp, sp, %lo(boot_stack_top)
+    # 用 tp 保存 hart 编号
+    mv tp, a0
+    # 每个 hart 使用各自的启动栈：sp = boot_stack + (hart 编号 + 1) * 启动栈大小
+    lui sp, %hi(boot_stack)
+    addi sp, sp, %lo(boot_stack)
+    addi t0, a0, 1
+    li t1, 4096 * 16
+    mul t0, t0, t1
+    add sp, sp, t0
     # 跳转至 rust_main
     # 这里同时伴随 hart 和 dtb_pa 两个指针的传入（是 OpenSBI 帮我们完成的）
     lui t0, %hi(rust_main)
@@ -32,12 +39,12 @@ _start:
     jr t0
 
     # 回忆：bss 段是 ELF 文件中只记录长度，而全部初始化为 0 的一段内存空间
-    # 这里声明字段 .bss.stack 作为操作系统启动时的栈
+    # 这里声明字段 .bss.stack 作为操作系统启动时的栈，每个 hart 一个（共 MAX_HART_COUNT 个）
     .section .bss.stack
     .global boot_stack
 boot_stack:
-    # 16K 启动栈大小
-    .space 4096 * 16
+    # 每个 hart 64K 启动栈大小
+    .space 4096 * 16 * 8
     .global boot_stack_top
 boot_stack_top:
     # 栈结尾
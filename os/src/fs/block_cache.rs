@@ -0,0 +1,134 @@
+//! 写回式的块设备缓存 [`BlockCache`]
+
+use super::*;
+use alloc::{boxed::Box, collections::VecDeque, vec};
+use rcore_fs::dev::{self, BlockDevice};
+
+/// 缓存中的一个块
+struct CachedBlock {
+    /// 块号
+    block_id: usize,
+    /// 块的数据
+    data: Box<[u8]>,
+    /// 是否被修改过而尚未写回设备
+    dirty: bool,
+}
+
+/// 写回式的块设备缓存
+///
+/// 读写都只经过缓存，被修改的块标记为脏块，直到被替换出缓存或者调用 [`BlockCache::sync`] 时才写回设备。
+/// 缓存已满时替换最久未使用的块
+pub struct BlockCache<T: BlockDevice> {
+    /// 实际的块设备
+    device: T,
+    /// 最多缓存的块数
+    capacity: usize,
+    /// 缓存的块，越靠后的越是最近使用的
+    blocks: Mutex<VecDeque<CachedBlock>>,
+}
+
+impl<T: BlockDevice> BlockCache<T> {
+    /// 为块设备创建最多缓存 `capacity` 个块的缓存
+    pub fn new(device: T, capacity: usize) -> Self {
+        assert!(capacity > 0);
+        Self {
+            device,
+            capacity,
+            blocks: Mutex::new(VecDeque::with_capacity(capacity)),
+        }
+    }
+
+    /// 将所有脏块写回设备，然后执行设备的同步
+    pub fn flush(&self) -> dev::Result<()> {
+        let mut blocks = self.blocks.lock();
+        for block in blocks.iter_mut().filter(|block| block.dirty) {
+            self.device.write_at(block.block_id, &block.data)?;
+            block.dirty = false;
+        }
+        self.device.sync()
+    }
+
+    /// 与 [`BlockCache::flush`] 相同，但缓存正被其他地方使用时不等待，直接返回 `None`
+    ///
+    /// 用于 panic 之后的写回，此时持有缓存的线程可能再也不会释放它
+    pub fn try_flush(&self) -> Option<dev::Result<()>> {
+        let mut blocks = self.blocks.try_lock()?;
+        for block in blocks.iter_mut().filter(|block| block.dirty) {
+            if let Err(error) = self.device.write_at(block.block_id, &block.data) {
+                return Some(Err(error));
+            }
+            block.dirty = false;
+        }
+        Some(self.device.sync())
+    }
+
+    /// 取出缓存中的块并记为最近使用，不在缓存中时从设备读入
+    ///
+    /// 缓存已满时先替换最久未使用的块，如果它是脏块则先写回
+    fn get<'a>(
+        &self,
+        blocks: &'a mut VecDeque<CachedBlock>,
+        block_id: usize,
+    ) -> dev::Result<&'a mut CachedBlock> {
+        if let Some(index) = blocks.iter().position(|block| block.block_id == block_id) {
+            let block = blocks.remove(index).unwrap();
+            blocks.push_back(block);
+        } else {
+            let mut block = if blocks.len() >= self.capacity {
+                let victim = blocks.front_mut().unwrap();
+                if victim.dirty {
+                    // 写回失败时保留在缓存中，数据不会丢失
+                    self.device.write_at(victim.block_id, &victim.data)?;
+                    victim.dirty = false;
+                }
+                blocks.pop_front().unwrap()
+            } else {
+                CachedBlock {
+                    block_id,
+                    data: vec![0u8; 1 << T::BLOCK_SIZE_LOG2].into_boxed_slice(),
+                    dirty: false,
+                }
+            };
+            self.device.read_at(block_id, &mut block.data)?;
+            block.block_id = block_id;
+            blocks.push_back(block);
+        }
+        Ok(blocks.back_mut().unwrap())
+    }
+}
+
+impl<T: BlockDevice> BlockDevice for BlockCache<T> {
+    /// 块大小与实际的块设备相同
+    const BLOCK_SIZE_LOG2: u8 = T::BLOCK_SIZE_LOG2;
+
+    /// 从缓存中读取某个块到 buf 中
+    ///
+    /// buf 可能是用户内存，访问时可能发生缺页，因此先复制出来，释放缓存的锁之后再写入 buf
+    fn read_at(&self, block_id: usize, buf: &mut [u8]) -> dev::Result<()> {
+        let data = {
+            let mut blocks = self.blocks.lock();
+            self.get(&mut blocks, block_id)?.data.clone()
+        };
+        let length = buf.len().min(data.len());
+        buf[..length].copy_from_slice(&data[..length]);
+        Ok(())
+    }
+
+    /// 将 buf 中的数据写入缓存中的块，并标记为脏块
+    ///
+    /// 与 [`BlockCache::read_at`] 相同，在取得缓存的锁之前先读出 buf
+    fn write_at(&self, block_id: usize, buf: &[u8]) -> dev::Result<()> {
+        let length = buf.len().min(1 << T::BLOCK_SIZE_LOG2);
+        let data = buf[..length].to_vec();
+        let mut blocks = self.blocks.lock();
+        let block = self.get(&mut blocks, block_id)?;
+        block.data[..length].copy_from_slice(&data);
+        block.dirty = true;
+        Ok(())
+    }
+
+    /// 将所有脏块写回设备（见 [`BlockCache::flush`]）
+    fn sync(&self) -> dev::Result<()> {
+        self.flush()
+    }
+}
@@ -1,4 +1,10 @@
 //! 文件系统的配置信息
 
-/// 块设备的 Cache 块个数
-pub const BLOCK_CACHE_CAPACITY: usize = 0x10;
+/// 块设备的 Cache 块个数（共 128 KB）
+pub const BLOCK_CACHE_CAPACITY: usize = 0x100;
+
+/// 定期写回文件系统的间隔，单位是时钟中断次数（所有 hart 的总和）
+pub const SYNC_INTERVAL: usize = 500;
+
+/// 管道缓冲区的字节数
+pub const PIPE_BUFFER_SIZE: usize = 0x1000;
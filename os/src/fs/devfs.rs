@@ -0,0 +1,450 @@
+//! 设备文件系统 [`DevFileSystem`]
+//!
+//! 根目录中包含 `null`、`zero`、`random`、`console`，以及 [`static@DRIVERS`] 中每个驱动对应的设备
+//! （交换区所在的磁盘除外），使用户程序可以通过路径打开设备。启动时挂载在 `/dev`
+
+use super::*;
+use crate::drivers::driver::Driver;
+use alloc::{format, sync::Weak};
+use rcore_fs::dev;
+use riscv::register::time;
+use spin::RwLock;
+
+/// 块设备读写的最小单位
+const SECTOR_SIZE: usize = 1 << <BlockDevice as dev::BlockDevice>::BLOCK_SIZE_LOG2;
+
+/// 设备文件系统
+pub struct DevFileSystem {
+    /// 根目录，创建文件系统之后立即设置
+    root: RwLock<Option<Arc<DevDirectory>>>,
+}
+
+/// 设备文件系统的根目录，其中的设备在创建文件系统时确定
+struct DevDirectory {
+    /// 所属的文件系统
+    fs: Weak<DevFileSystem>,
+    /// 设备名和设备，不含 `.` 和 `..`
+    entries: Vec<(String, Arc<DevNode>)>,
+}
+
+/// 根目录中的一个设备，为设备提供文件信息
+struct DevNode {
+    /// INode 编号
+    id: usize,
+    /// 字符设备或块设备
+    type_: FileType,
+    /// 所属的文件系统
+    fs: Weak<DevFileSystem>,
+    /// 实际的设备
+    device: Arc<dyn INode>,
+}
+
+impl DevFileSystem {
+    /// 创建设备文件系统，按照当前的驱动列表生成设备
+    pub fn new() -> Arc<Self> {
+        let fs = Arc::new(Self {
+            root: RwLock::new(None),
+        });
+        let char_devices: [(&str, Arc<dyn INode>); 4] = [
+            ("null", Arc::new(Null)),
+            ("zero", Arc::new(Zero)),
+            ("random", Arc::new(Random::new())),
+            ("console", Arc::new(Console)),
+        ];
+        let mut devices: Vec<(String, FileType, Arc<dyn INode>)> = char_devices
+            .iter()
+            .map(|(name, device)| (String::from(*name), FileType::CharDevice, device.clone()))
+            .collect();
+        let mut block_count = 0;
+        for driver in DRIVERS.read().iter() {
+            match driver.device_type() {
+                DeviceType::Block => {
+                    // 与 `diskN` 挂载来源的编号相同。交换区的内容属于各个进程，不能暴露给用户程序
+                    if block_count != 1 {
+                        let name = format!("disk{}", block_count);
+                        let disk = Disk {
+                            driver: driver.clone(),
+                            name: name.clone(),
+                            root: block_count == 0,
+                        };
+                        let disk = Arc::new(disk) as Arc<dyn INode>;
+                        devices.push((name, FileType::BlockDevice, disk));
+                    }
+                    block_count += 1;
+                }
+            }
+        }
+        let entries = devices
+            .into_iter()
+            .enumerate()
+            .map(|(index, (name, type_, device))| {
+                let node = Arc::new(DevNode {
+                    // 1 为根目录
+                    id: index + 2,
+                    type_,
+                    fs: Arc::downgrade(&fs),
+                    device,
+                });
+                (name, node)
+            })
+            .collect();
+        *fs.root.write() = Some(Arc::new(DevDirectory {
+            fs: Arc::downgrade(&fs),
+            entries,
+        }));
+        fs
+    }
+}
+
+impl FileSystem for DevFileSystem {
+    /// 设备都直接读写，不需要写回
+    fn sync(&self) -> Result<()> {
+        Ok(())
+    }
+
+    fn root_inode(&self) -> Arc<dyn INode> {
+        self.root.read().clone().unwrap()
+    }
+
+    fn info(&self) -> FsInfo {
+        FsInfo {
+            bsize: 0,
+            frsize: 0,
+            blocks: 0,
+            bfree: 0,
+            bavail: 0,
+            files: self.root.read().as_ref().unwrap().entries.len() + 1,
+            ffree: 0,
+            namemax: NAME_MAX,
+        }
+    }
+}
+
+/// 设备文件系统中的文件信息，大小都记为 0
+fn metadata(id: usize, type_: FileType, mode: u16) -> Metadata {
+    let time = Timespec { sec: 0, nsec: 0 };
+    Metadata {
+        dev: 0,
+        inode: id,
+        size: 0,
+        blk_size: 0,
+        blocks: 0,
+        atime: time,
+        mtime: time,
+        ctime: time,
+        type_,
+        mode,
+        nlinks: 1,
+        uid: 0,
+        gid: 0,
+        rdev: 0,
+    }
+}
+
+impl INode for DevDirectory {
+    fn read_at(&self, _offset: usize, _buf: &mut [u8]) -> Result<usize> {
+        Err(FsError::NotFile)
+    }
+
+    fn write_at(&self, _offset: usize, _buf: &[u8]) -> Result<usize> {
+        Err(FsError::NotFile)
+    }
+
+    fn poll(&self) -> Result<PollStatus> {
+        Err(FsError::NotFile)
+    }
+
+    fn metadata(&self) -> Result<Metadata> {
+        Ok(metadata(1, FileType::Dir, 0o755))
+    }
+
+    fn find(&self, name: &str) -> Result<Arc<dyn INode>> {
+        match name {
+            "." | ".." => Ok(self.fs().root_inode()),
+            _ => self
+                .entries
+                .iter()
+                .find(|(entry, _)| entry == name)
+                .map(|(_, node)| node.clone() as Arc<dyn INode>)
+                .ok_or(FsError::EntryNotFound),
+        }
+    }
+
+    /// 第 0、1 项为 `.` 和 `..`，之后是各个设备
+    fn get_entry(&self, id: usize) -> Result<String> {
+        match id {
+            0 => Ok(String::from(".")),
+            1 => Ok(String::from("..")),
+            _ => self
+                .entries
+                .get(id - 2)
+                .map(|(name, _)| name.clone())
+                .ok_or(FsError::EntryNotFound),
+        }
+    }
+
+    fn fs(&self) -> Arc<dyn FileSystem> {
+        self.fs.upgrade().unwrap()
+    }
+
+    /// This is used to implement dynamics cast.
+    /// Simply return self in the implement of the function.
+    fn as_any_ref(&self) -> &dyn Any {
+        self
+    }
+}
+
+impl INode for DevNode {
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> Result<usize> {
+        self.device.read_at(offset, buf)
+    }
+
+    fn write_at(&self, offset: usize, buf: &[u8]) -> Result<usize> {
+        self.device.write_at(offset, buf)
+    }
+
+    fn poll(&self) -> Result<PollStatus> {
+        self.device.poll()
+    }
+
+    fn metadata(&self) -> Result<Metadata> {
+        Ok(metadata(self.id, self.type_, 0o666))
+    }
+
+    /// 设备都直接读写，不需要写回
+    fn sync_all(&self) -> Result<()> {
+        Ok(())
+    }
+
+    /// 设备都直接读写，不需要写回
+    fn sync_data(&self) -> Result<()> {
+        Ok(())
+    }
+
+    fn fs(&self) -> Arc<dyn FileSystem> {
+        self.fs.upgrade().unwrap()
+    }
+
+    /// This is used to implement dynamics cast.
+    /// Simply return self in the implement of the function.
+    fn as_any_ref(&self) -> &dyn Any {
+        self
+    }
+}
+
+/// `/dev/null`：读取时总是到达末尾，写入的数据被丢弃
+struct Null;
+
+impl INode for Null {
+    fn read_at(&self, _offset: usize, _buf: &mut [u8]) -> Result<usize> {
+        Ok(0)
+    }
+
+    fn write_at(&self, _offset: usize, buf: &[u8]) -> Result<usize> {
+        Ok(buf.len())
+    }
+
+    fn poll(&self) -> Result<PollStatus> {
+        Ok(PollStatus {
+            read: true,
+            write: true,
+            error: false,
+        })
+    }
+
+    /// This is used to implement dynamics cast.
+    /// Simply return self in the implement of the function.
+    fn as_any_ref(&self) -> &dyn Any {
+        self
+    }
+}
+
+/// `/dev/zero`：读取时得到 0，写入的数据被丢弃
+struct Zero;
+
+impl INode for Zero {
+    fn read_at(&self, _offset: usize, buf: &mut [u8]) -> Result<usize> {
+        buf.iter_mut().for_each(|byte| *byte = 0);
+        Ok(buf.len())
+    }
+
+    fn write_at(&self, _offset: usize, buf: &[u8]) -> Result<usize> {
+        Ok(buf.len())
+    }
+
+    fn poll(&self) -> Result<PollStatus> {
+        Ok(PollStatus {
+            read: true,
+            write: true,
+            error: false,
+        })
+    }
+
+    /// This is used to implement dynamics cast.
+    /// Simply return self in the implement of the function.
+    fn as_any_ref(&self) -> &dyn Any {
+        self
+    }
+}
+
+/// `/dev/random`：读取时得到伪随机数（xorshift64*），写入的数据混入随机数状态
+///
+/// 以创建时的时钟作为种子，不能用于密码学用途
+struct Random {
+    /// 随机数生成器的状态，不为 0
+    state: Mutex<u64>,
+}
+
+impl Random {
+    fn new() -> Self {
+        Self {
+            state: Mutex::new(time::read() as u64 | 1),
+        }
+    }
+}
+
+impl INode for Random {
+    fn read_at(&self, _offset: usize, buf: &mut [u8]) -> Result<usize> {
+        let mut state = self.state.lock();
+        for chunk in buf.chunks_mut(8) {
+            *state ^= *state >> 12;
+            *state ^= *state << 25;
+            *state ^= *state >> 27;
+            let value = state.wrapping_mul(0x2545_f491_4f6c_dd1d).to_le_bytes();
+            chunk.copy_from_slice(&value[..chunk.len()]);
+        }
+        Ok(buf.len())
+    }
+
+    fn write_at(&self, _offset: usize, buf: &[u8]) -> Result<usize> {
+        let mut state = self.state.lock();
+        for &byte in buf {
+            *state = state.rotate_left(8) ^ byte as u64;
+        }
+        if *state == 0 {
+            *state = 1;
+        }
+        Ok(buf.len())
+    }
+
+    fn poll(&self) -> Result<PollStatus> {
+        Ok(PollStatus {
+            read: true,
+            write: true,
+            error: false,
+        })
+    }
+
+    /// This is used to implement dynamics cast.
+    /// Simply return self in the implement of the function.
+    fn as_any_ref(&self) -> &dyn Any {
+        self
+    }
+}
+
+/// `/dev/console`：从 [`static@STDIN`] 读取，向 [`static@STDOUT`] 输出
+struct Console;
+
+impl INode for Console {
+    fn read_at(&self, _offset: usize, buf: &mut [u8]) -> Result<usize> {
+        STDIN.read_at(0, buf)
+    }
+
+    fn write_at(&self, _offset: usize, buf: &[u8]) -> Result<usize> {
+        STDOUT.write_at(0, buf)
+    }
+
+    fn poll(&self) -> Result<PollStatus> {
+        Err(FsError::NotSupported)
+    }
+
+    /// This is used to implement dynamics cast.
+    /// Simply return self in the implement of the function.
+    fn as_any_ref(&self) -> &dyn Any {
+        self
+    }
+}
+
+/// `/dev/diskN`：直接读写块设备，位置以字节计
+///
+/// 读写不经过 [`BlockCache`]，缓存中可能有尚未写回的数据。
+/// 因此根文件系统所在的磁盘和已经挂载的磁盘都不能读写，返回 [`FsError::Busy`]
+struct Disk {
+    /// 块设备的驱动
+    driver: Arc<dyn Driver>,
+    /// 设备名，也是挂载时的来源，例如 `disk2`
+    name: String,
+    /// 是否为根文件系统所在的磁盘
+    root: bool,
+}
+
+impl Disk {
+    /// 磁盘是否正被文件系统使用
+    fn busy(&self) -> bool {
+        self.root || is_source_mounted(&self.name)
+    }
+}
+
+impl INode for Disk {
+    /// 读取失败（例如超出设备末尾）时停止，返回已经读取的长度
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> Result<usize> {
+        if self.busy() {
+            return Err(FsError::Busy);
+        }
+        let mut sector = [0u8; SECTOR_SIZE];
+        let mut read = 0;
+        while read < buf.len() {
+            let position = offset + read;
+            if !self.driver.read_block(position / SECTOR_SIZE, &mut sector) {
+                break;
+            }
+            let start = position % SECTOR_SIZE;
+            let size = (SECTOR_SIZE - start).min(buf.len() - read);
+            buf[read..read + size].copy_from_slice(&sector[start..start + size]);
+            read += size;
+        }
+        Ok(read)
+    }
+
+    /// 不足一块的部分先读出整块再写回。一个字节都没有写入时返回 [`FsError::DeviceError`]
+    fn write_at(&self, offset: usize, buf: &[u8]) -> Result<usize> {
+        if self.busy() {
+            return Err(FsError::Busy);
+        }
+        let mut sector = [0u8; SECTOR_SIZE];
+        let mut written = 0;
+        while written < buf.len() {
+            let position = offset + written;
+            let block_id = position / SECTOR_SIZE;
+            let start = position % SECTOR_SIZE;
+            let size = (SECTOR_SIZE - start).min(buf.len() - written);
+            if size < SECTOR_SIZE && !self.driver.read_block(block_id, &mut sector) {
+                break;
+            }
+            sector[start..start + size].copy_from_slice(&buf[written..written + size]);
+            if !self.driver.write_block(block_id, &sector) {
+                break;
+            }
+            written += size;
+        }
+        if written == 0 && !buf.is_empty() {
+            return Err(FsError::DeviceError);
+        }
+        Ok(written)
+    }
+
+    fn poll(&self) -> Result<PollStatus> {
+        let busy = self.busy();
+        Ok(PollStatus {
+            read: !busy,
+            write: !busy,
+            error: false,
+        })
+    }
+
+    /// This is used to implement dynamics cast.
+    /// Simply return self in the implement of the function.
+    fn as_any_ref(&self) -> &dyn Any {
+        self
+    }
+}
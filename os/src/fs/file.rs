@@ -0,0 +1,184 @@
+//! 打开的文件 [`FileHandle`]
+//!
+//! 进程的文件描述符指向 [`FileHandle`]，它在 [`INode`] 之上记录读写位置和打开方式。
+//! fork 或复制描述符之后，多个描述符可以共享同一个 [`FileHandle`]，也就共享读写位置。
+
+use super::*;
+use bitflags::*;
+
+bitflags! {
+    /// 打开文件的方式
+    pub struct OpenFlags: usize {
+        /// 可读
+        const READ = 1 << 0;
+        /// 可写
+        const WRITE = 1 << 1;
+        /// 每次写入前都先移动到文件末尾
+        const APPEND = 1 << 2;
+        /// 执行 exec 时关闭
+        const CLOEXEC = 1 << 3;
+        /// 文件不存在时创建
+        const CREATE = 1 << 4;
+        /// 打开时将文件截断为空（需要可写）
+        const TRUNCATE = 1 << 5;
+        /// 与 [`OpenFlags::CREATE`] 一同使用，文件已经存在时失败
+        const EXCLUSIVE = 1 << 6;
+    }
+}
+
+/// 移动读写位置时的基准
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum SeekFrom {
+    /// 相对文件开头
+    Start(usize),
+    /// 相对当前位置
+    Current(isize),
+    /// 相对文件末尾
+    End(isize),
+}
+
+/// 打开的文件
+pub struct FileHandle {
+    /// 文件对应的 INode
+    pub inode: Arc<dyn INode>,
+    /// 打开的方式
+    pub flags: OpenFlags,
+    /// 是否可以移动读写位置
+    ///
+    /// 只有普通文件、目录和块设备才可以，目录的位置是下一个要读取的目录项的序号。
+    /// 其他的如控制台等，每次都从位置 0 读写
+    seekable: bool,
+    /// 当前的读写位置
+    offset: Mutex<usize>,
+}
+
+impl FileHandle {
+    /// 以给定的方式打开 INode
+    pub fn new(inode: Arc<dyn INode>, flags: OpenFlags) -> Arc<Self> {
+        let seekable = match inode.metadata() {
+            Ok(metadata) => matches!(
+                metadata.type_,
+                FileType::File | FileType::Dir | FileType::BlockDevice
+            ),
+            Err(_) => false,
+        };
+        Arc::new(Self {
+            inode,
+            flags,
+            seekable,
+            offset: Mutex::new(0),
+        })
+    }
+
+    /// 按照 `flags` 打开 `path` 处的文件，相对路径从 `directory` 开始查找
+    ///
+    /// 目录只能以只读方式打开
+    pub fn open(directory: &Arc<dyn INode>, path: &str, flags: OpenFlags) -> Result<Arc<Self>> {
+        let inode = match lookup(directory, path) {
+            Ok(_) if flags.contains(OpenFlags::CREATE | OpenFlags::EXCLUSIVE) => {
+                return Err(FsError::EntryExist)
+            }
+            Ok(inode) => inode,
+            Err(FsError::EntryNotFound) if flags.contains(OpenFlags::CREATE) => {
+                // 在上级目录中创建文件
+                let (parent, name) = lookup_parent(directory, path)?;
+                parent.create(name, FileType::File, 0o666)?
+            }
+            Err(error) => return Err(error),
+        };
+        if inode.metadata()?.type_ == FileType::Dir
+            && flags.intersects(OpenFlags::WRITE | OpenFlags::APPEND | OpenFlags::TRUNCATE)
+        {
+            return Err(FsError::IsDir);
+        }
+        if flags.contains(OpenFlags::TRUNCATE) {
+            if !flags.contains(OpenFlags::WRITE) {
+                return Err(FsError::InvalidParam);
+            }
+            inode.resize(0)?;
+        }
+        Ok(Self::new(inode, flags))
+    }
+
+    /// 从当前位置读取，并将位置后移读取的长度
+    pub fn read(&self, buffer: &mut [u8]) -> Result<usize> {
+        if !self.flags.contains(OpenFlags::READ) {
+            return Err(FsError::InvalidParam);
+        }
+        if !self.seekable {
+            // 读取时可能休眠等待（例如控制台），不能持有锁
+            return self.inode.read_at(0, buffer);
+        }
+        let mut offset = self.offset.lock();
+        let size = self.inode.read_at(*offset, buffer)?;
+        *offset += size;
+        Ok(size)
+    }
+
+    /// 在当前位置写入，并将位置后移写入的长度
+    ///
+    /// 以 [`OpenFlags::APPEND`] 打开时，总是写入到文件末尾
+    pub fn write(&self, buffer: &[u8]) -> Result<usize> {
+        if !self.flags.contains(OpenFlags::WRITE) {
+            return Err(FsError::InvalidParam);
+        }
+        if !self.seekable {
+            return self.inode.write_at(0, buffer);
+        }
+        let mut offset = self.offset.lock();
+        if self.flags.contains(OpenFlags::APPEND) {
+            *offset = self.inode.metadata()?.size;
+        }
+        let size = self.inode.write_at(*offset, buffer)?;
+        *offset += size;
+        Ok(size)
+    }
+
+    /// 读取目录中当前位置的目录项，并将位置后移一项，已经读完时返回 `None`
+    pub fn read_entry(&self) -> Result<Option<DirEntry>> {
+        if !self.flags.contains(OpenFlags::READ) {
+            return Err(FsError::InvalidParam);
+        }
+        if self.inode.metadata()?.type_ != FileType::Dir {
+            return Err(FsError::NotDir);
+        }
+        let mut offset = self.offset.lock();
+        let name = match self.inode.get_entry(*offset) {
+            Ok(name) => name,
+            Err(FsError::EntryNotFound) => return Ok(None),
+            Err(error) => return Err(error),
+        };
+        let metadata = self.inode.find(&name)?.metadata()?;
+        *offset += 1;
+        Ok(Some(DirEntry::new(&name, &metadata)))
+    }
+
+    /// 将文件的修改写回设备
+    ///
+    /// 文件所在的整个文件系统都会写回。控制台、管道等不支持
+    pub fn sync(&self) -> Result<()> {
+        if !self.seekable {
+            return Err(FsError::NotSupported);
+        }
+        self.inode.sync_all()?;
+        self.inode.fs().sync()
+    }
+
+    /// 移动读写位置，返回新的位置
+    pub fn seek(&self, position: SeekFrom) -> Result<usize> {
+        if !self.seekable {
+            return Err(FsError::NotSupported);
+        }
+        let mut offset = self.offset.lock();
+        let new_offset = match position {
+            SeekFrom::Start(start) => start as isize,
+            SeekFrom::Current(delta) => *offset as isize + delta,
+            SeekFrom::End(delta) => self.inode.metadata()?.size as isize + delta,
+        };
+        if new_offset < 0 {
+            return Err(FsError::InvalidParam);
+        }
+        *offset = new_offset as usize;
+        Ok(*offset)
+    }
+}
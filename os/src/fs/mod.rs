@@ -1,49 +1,205 @@
 //! 文件系统
 //!
-//! 将读取第一个块设备作为根文件系统
+//! 将读取第一个块设备作为根文件系统，没有块设备时以 [`TmpFileSystem`] 作为根文件系统。
+//! 其他文件系统可以挂载到其中的目录上（见 [`mount()`]），启动时在 `/tmp` 挂载 [`TmpFileSystem`]，
+//! 在 `/dev` 挂载 [`DevFileSystem`]。
+//! 设备的读写经过写回式的 [`BlockCache`]，由内核线程 [`sync_thread`] 定期写回，关机前也会写回一次
 
 use crate::drivers::{
     block::BlockDevice,
     driver::{DeviceType, DRIVERS},
 };
 use crate::kernel::Condvar;
-use alloc::{sync::Arc, vec::Vec};
+use crate::process::Lock;
+use alloc::{string::String, sync::Arc, vec::Vec};
 use core::any::Any;
+use core::sync::atomic::{AtomicBool, Ordering};
 use lazy_static::lazy_static;
 use rcore_fs_sfs::SimpleFileSystem;
 use spin::Mutex;
 
+mod block_cache;
 mod config;
+mod devfs;
+mod file;
 mod inode_ext;
+mod mount;
+mod pipe;
+mod stat;
 mod stdin;
 mod stdout;
+mod tmpfs;
 
+pub use block_cache::BlockCache;
 pub use config::*;
+pub use devfs::DevFileSystem;
+pub use file::{FileHandle, OpenFlags, SeekFrom};
 pub use inode_ext::INodeExt;
-pub use rcore_fs::{dev::block_cache::BlockCache, vfs::*};
+pub use mount::{
+    is_mounted, is_source_mounted, lookup, mount, sync_mounts, try_flush_mounts, umount,
+};
+pub use pipe::Pipe;
+pub use rcore_fs::vfs::*;
+pub use stat::*;
 pub use stdin::STDIN;
 pub use stdout::STDOUT;
+pub use tmpfs::TmpFileSystem;
 
 lazy_static! {
+    /// 根文件系统所在块设备（第一个块设备）的缓存，没有块设备时为 `None`
+    static ref ROOT_DEVICE: Option<Arc<BlockCache<BlockDevice>>> = block_device(0)
+        // 动态分配一段内存空间作为设备 Cache
+        .map(|device| Arc::new(BlockCache::new(device, BLOCK_CACHE_CAPACITY)));
+    /// 根文件系统，没有块设备时为空的 [`TmpFileSystem`]
+    pub static ref ROOT_FS: Arc<dyn FileSystem> = match ROOT_DEVICE.as_ref() {
+        Some(device) => SimpleFileSystem::open(device.clone()).expect("failed to open SFS"),
+        None => {
+            println!("no block device found, using tmpfs as root");
+            TmpFileSystem::new()
+        }
+    };
     /// 根文件系统的根目录的 INode
-    pub static ref ROOT_INODE: Arc<dyn INode> = {
-        // 选择第一个块设备
-        for driver in DRIVERS.read().iter() {
-            if driver.device_type() == DeviceType::Block {
-                let device = BlockDevice(driver.clone());
-                // 动态分配一段内存空间作为设备 Cache
-                let device_with_cache = Arc::new(BlockCache::new(device, BLOCK_CACHE_CAPACITY));
-                return SimpleFileSystem::open(device_with_cache)
-                    .expect("failed to open SFS")
-                    .root_inode();
+    pub static ref ROOT_INODE: Arc<dyn INode> = ROOT_FS.root_inode();
+    /// 每隔 [`SYNC_INTERVAL`] 次时钟中断唤醒一次 [`sync_thread`]
+    pub static ref SYNC_CONDVAR: Condvar = Condvar::default();
+    /// [`sync_thread`] 写回时持有，以关闭中断
+    static ref SYNC_LOCK: Lock<()> = Lock::new(());
+}
+
+/// 根文件系统是否已经初始化
+static INITIALIZED: AtomicBool = AtomicBool::new(false);
+
+/// 第 `index` 个块设备（从 0 开始）
+pub fn block_device(index: usize) -> Option<BlockDevice> {
+    DRIVERS
+        .read()
+        .iter()
+        .filter(|driver| driver.device_type() == DeviceType::Block)
+        .nth(index)
+        .map(|driver| BlockDevice(driver.clone()))
+}
+
+/// 将根文件系统和所有被挂载的文件系统的修改写回设备
+pub fn sync() -> Result<()> {
+    ROOT_FS.sync()?;
+    if let Some(device) = ROOT_DEVICE.as_ref() {
+        device.flush().map_err(|_| FsError::DeviceError)?;
+    }
+    sync_mounts()
+}
+
+/// 定期写回文件系统的内核线程
+///
+/// 其他线程全部结束后退出，以便系统关机
+pub fn sync_thread() {
+    while crate::process::thread_count() > 1 {
+        SYNC_CONDVAR.wait_kernel_thread();
+        // 内核线程会被时钟中断打断，持有文件系统的锁时被切换出去可能导致死锁，因此写回时关闭中断
+        let _lock = SYNC_LOCK.lock();
+        if let Err(error) = sync() {
+            println!("failed to sync fs: {:?}", error);
+        }
+    }
+}
+
+/// 关机前将所有文件系统的修改写回设备
+///
+/// 文件系统尚未初始化时不做任何事。只执行一次，避免写回过程中再次 panic 时重复写回。
+///
+/// 所有线程都已结束时完整地写回。否则是出错 panic，panic 的线程可能持有文件系统或者缓存的锁，
+/// 此时只尝试写回各个设备的缓存，正被使用的缓存直接跳过，以免关机时卡住
+pub fn sync_before_shutdown() {
+    static SYNCED: AtomicBool = AtomicBool::new(false);
+    if !INITIALIZED.load(Ordering::Acquire) || SYNCED.swap(true, Ordering::AcqRel) {
+        return;
+    }
+    if crate::process::thread_count() == 0 {
+        if let Err(error) = sync() {
+            println!("failed to sync fs before shutdown: {:?}", error);
+        }
+        return;
+    }
+    if let Some(device) = ROOT_DEVICE.as_ref() {
+        if !matches!(device.try_flush(), Some(Ok(()))) {
+            println!("skipped flushing the root device");
+        }
+    }
+    try_flush_mounts();
+}
+
+/// 找到 `path` 的上级目录，返回上级目录和路径的最后一项
+///
+/// 例如 `/a/b/c` 返回 `/a/b` 和 `c`。相对路径从 `directory` 开始查找
+pub fn lookup_parent<'a>(
+    directory: &Arc<dyn INode>,
+    path: &'a str,
+) -> Result<(Arc<dyn INode>, &'a str)> {
+    let path = path.trim_end_matches('/');
+    match path.rfind('/') {
+        Some(index) => Ok((lookup(directory, &path[..=index])?, &path[index + 1..])),
+        None => Ok((directory.clone(), path)),
+    }
+}
+
+/// 将 `path` 接在绝对路径 `base` 之后，返回化简后的绝对路径
+///
+/// `path` 为绝对路径时忽略 `base`。路径中的 `.` 和 `..` 会被消去，根目录的 `..` 仍为根目录
+pub fn join_path(base: &str, path: &str) -> String {
+    let mut components: Vec<&str> = Vec::new();
+    let prefix = if path.starts_with('/') { "" } else { base };
+    for component in prefix.split('/').chain(path.split('/')) {
+        match component {
+            "" | "." => {}
+            ".." => {
+                components.pop();
             }
+            _ => components.push(component),
         }
-        panic!("failed to load fs")
-    };
+    }
+    let mut result = String::new();
+    for component in components {
+        result.push('/');
+        result.push_str(component);
+    }
+    if result.is_empty() {
+        result.push('/');
+    }
+    result
 }
 
-/// 触发 [`static@ROOT_INODE`] 的初始化并打印根目录内容
+/// 触发 [`static@ROOT_INODE`] 的初始化并打印根目录内容，然后挂载 `/tmp` 和 `/dev`
+///
+/// 存在第三个块设备时，将其作为测试程序所用的数据（见 `user/fixtures`）挂载到 `/fixtures`
 pub fn init() {
     ROOT_INODE.ls();
+    mount_at_boot("tmp", "tmpfs");
+    mount_at_boot("dev", "devfs");
+    if block_device(2).is_some() {
+        mount_at_boot("fixtures", "disk2");
+    }
+    INITIALIZED.store(true, Ordering::Release);
     println!("mod fs initialized");
 }
+
+/// 在根目录中的 `name` 目录上挂载 `source`
+///
+/// 根文件系统在块设备上时，只挂载已经存在的目录；根文件系统是 [`TmpFileSystem`] 时，
+/// 目录不存在则先创建
+fn mount_at_boot(name: &str, source: &str) {
+    let point = match ROOT_INODE.find(name) {
+        Ok(point) => point,
+        Err(FsError::EntryNotFound) if ROOT_DEVICE.is_none() => {
+            match ROOT_INODE.create(name, FileType::Dir, 0o755) {
+                Ok(point) => point,
+                Err(error) => {
+                    println!("failed to create /{}: {:?}", name, error);
+                    return;
+                }
+            }
+        }
+        Err(_) => return,
+    };
+    if let Err(error) = mount(source, point) {
+        println!("failed to mount {} on /{}: {:?}", source, name, error);
+    }
+}
@@ -0,0 +1,220 @@
+//! 挂载表和跨越挂载点的路径查找 [`lookup`]
+//!
+//! 其他文件系统可以挂载到某个目录上，此后经过这个目录的路径都会进入被挂载的文件系统，
+//! 而原来的目录被遮住，直到卸载
+
+use super::*;
+use spin::RwLock;
+
+lazy_static! {
+    /// 挂载表，后挂载的在后面
+    static ref MOUNTS: RwLock<Vec<Mount>> = RwLock::new(Vec::new());
+}
+
+/// 标识一个 INode：所在文件系统的地址，以及在文件系统中的 INode 编号
+type INodeID = (usize, usize);
+
+/// 一个挂载
+struct Mount {
+    /// 来源，例如 `disk2`（见 [`open_filesystem`]）
+    source: String,
+    /// 挂载点目录
+    point: Arc<dyn INode>,
+    /// 挂载点目录的标识
+    point_id: INodeID,
+    /// 挂载的文件系统
+    fs: Arc<dyn FileSystem>,
+    /// 挂载的文件系统根目录的标识
+    root_id: INodeID,
+    /// 文件系统所在的设备，写回时一并写回，不在设备上的文件系统为 `None`
+    device: Option<Arc<BlockCache<BlockDevice>>>,
+}
+
+/// 查找 `path` 处的 INode，相对路径从 `directory` 开始查找
+///
+/// 进入挂载点时会转到被挂载的文件系统的根目录；在被挂载的文件系统的根目录中查找 `..` 时，
+/// 会回到挂载点的上级目录
+pub fn lookup(directory: &Arc<dyn INode>, path: &str) -> Result<Arc<dyn INode>> {
+    let mut current = if path.starts_with('/') {
+        ROOT_INODE.clone()
+    } else {
+        directory.clone()
+    };
+    for name in path.split('/') {
+        current = match name {
+            "" | "." => continue,
+            ".." => parent(&current)?,
+            _ => enter(current.find(name)?)?,
+        };
+    }
+    Ok(current)
+}
+
+/// 将 `source` 对应的文件系统挂载到目录 `point` 上
+///
+/// 根目录不能作为挂载点，同一个块设备不能同时挂载到多处；每次挂载 `tmpfs` 或 `devfs` 都是新的文件系统
+pub fn mount(source: &str, point: Arc<dyn INode>) -> Result<()> {
+    let point_id = inode_id(&point)?;
+    if point_id == inode_id(&ROOT_INODE)? {
+        return Err(FsError::Busy);
+    }
+    let mut mounts = MOUNTS.write();
+    if source.starts_with("disk") && mounts.iter().any(|mount| mount.source == source) {
+        return Err(FsError::Busy);
+    }
+    let (fs, device) = open_filesystem(source)?;
+    let root_id = inode_id(&fs.root_inode())?;
+    mounts.push(Mount {
+        source: String::from(source),
+        point,
+        point_id,
+        fs,
+        root_id,
+        device,
+    });
+    Ok(())
+}
+
+/// 卸载以 `root` 为根目录的文件系统，卸载前将其写回
+///
+/// `root` 不是被挂载的文件系统的根目录时返回 [`FsError::InvalidParam`]；
+/// 文件系统中还挂载有其他文件系统，或者还有其他 INode 被引用（打开的文件、工作目录）时返回 [`FsError::Busy`]
+pub fn umount(root: &Arc<dyn INode>) -> Result<()> {
+    let root_id = inode_id(root)?;
+    let mut mounts = MOUNTS.write();
+    let index = mounts
+        .iter()
+        .rposition(|mount| mount.root_id == root_id)
+        .ok_or(FsError::InvalidParam)?;
+    if mounts.iter().any(|mount| mount.point_id.0 == root_id.0) {
+        return Err(FsError::Busy);
+    }
+    // 块设备上的文件系统中，每个 INode 都持有文件系统的引用。除了挂载表和 `root` 之外还有引用时，
+    // 卸载之后对这些 INode 的写入只会留在不再被写回的缓存中
+    if Arc::strong_count(&mounts[index].fs) > 2 {
+        return Err(FsError::Busy);
+    }
+    sync_mount(&mounts[index])?;
+    mounts.remove(index);
+    Ok(())
+}
+
+/// `inode` 是否为某个被挂载的文件系统的根目录
+pub fn is_mounted(inode: &Arc<dyn INode>) -> bool {
+    let mounts = MOUNTS.read();
+    match inode_id(inode) {
+        Ok(id) => mounts.iter().any(|mount| mount.root_id == id),
+        Err(_) => false,
+    }
+}
+
+/// 来源 `source`（例如 `disk2`）是否已经被挂载
+pub fn is_source_mounted(source: &str) -> bool {
+    MOUNTS.read().iter().any(|mount| mount.source == source)
+}
+
+/// 将所有被挂载的文件系统写回
+pub fn sync_mounts() -> Result<()> {
+    for mount in MOUNTS.read().iter() {
+        sync_mount(mount)?;
+    }
+    Ok(())
+}
+
+/// 将所有被挂载的文件系统所在设备的缓存写回，不写回文件系统本身
+///
+/// 用于 panic 之后：挂载表或者缓存正被其他地方使用时跳过，而不是等待
+pub fn try_flush_mounts() {
+    let mounts = match MOUNTS.try_read() {
+        Some(mounts) => mounts,
+        None => return,
+    };
+    for device in mounts.iter().filter_map(|mount| mount.device.as_ref()) {
+        if !matches!(device.try_flush(), Some(Ok(()))) {
+            println!("skipped flushing a mounted device");
+        }
+    }
+}
+
+/// 将一个被挂载的文件系统及其设备写回
+fn sync_mount(mount: &Mount) -> Result<()> {
+    mount.fs.sync()?;
+    if let Some(device) = &mount.device {
+        device.flush().map_err(|_| FsError::DeviceError)?;
+    }
+    Ok(())
+}
+
+/// 根据来源打开文件系统
+///
+/// - `diskN`：第 N 个块设备（从 0 开始）上的 SFS。
+///   第 0 个是根文件系统，第 1 个是交换区（见 [`crate::memory::swap`]），都不能挂载
+/// - `tmpfs`：新建的空 [`TmpFileSystem`]
+/// - `devfs`：新建的 [`DevFileSystem`]
+fn open_filesystem(
+    source: &str,
+) -> Result<(Arc<dyn FileSystem>, Option<Arc<BlockCache<BlockDevice>>>)> {
+    if let Some(index) = source.strip_prefix("disk") {
+        let index: usize = index.parse().map_err(|_| FsError::InvalidParam)?;
+        if index < 2 {
+            return Err(FsError::Busy);
+        }
+        let device = block_device(index).ok_or(FsError::NoDevice)?;
+        let device = Arc::new(BlockCache::new(device, BLOCK_CACHE_CAPACITY));
+        let fs = SimpleFileSystem::open(device.clone())?;
+        return Ok((fs, Some(device)));
+    }
+    if source == "tmpfs" {
+        let fs: Arc<dyn FileSystem> = TmpFileSystem::new();
+        return Ok((fs, None));
+    }
+    if source == "devfs" {
+        let fs: Arc<dyn FileSystem> = DevFileSystem::new();
+        return Ok((fs, None));
+    }
+    Err(FsError::InvalidParam)
+}
+
+/// 如果 `inode` 是挂载点，则转到被挂载的文件系统的根目录
+fn enter(mut inode: Arc<dyn INode>) -> Result<Arc<dyn INode>> {
+    let mounts = MOUNTS.read();
+    if mounts.is_empty() || inode.metadata()?.type_ != FileType::Dir {
+        return Ok(inode);
+    }
+    // 同一个目录上可能先后挂载了多个文件系统，最后挂载的生效
+    loop {
+        let id = inode_id(&inode)?;
+        match mounts.iter().rev().find(|mount| mount.point_id == id) {
+            Some(mount) => inode = mount.fs.root_inode(),
+            None => return Ok(inode),
+        }
+    }
+}
+
+/// 目录 `inode` 的上级目录
+fn parent(inode: &Arc<dyn INode>) -> Result<Arc<dyn INode>> {
+    let mounts = MOUNTS.read();
+    let mut inode = inode.clone();
+    // 在被挂载的文件系统的根目录中，先回到挂载点
+    loop {
+        let id = inode_id(&inode)?;
+        match mounts.iter().rev().find(|mount| mount.root_id == id) {
+            Some(mount) => inode = mount.point.clone(),
+            None => return inode.find(".."),
+        }
+    }
+}
+
+/// 获得目录的标识
+///
+/// 只有目录才可能是挂载点或文件系统的根目录，其他 INode 返回 [`FsError::NotDir`]。
+/// 控制台等不属于任何文件系统的 INode 也在这里被排除
+fn inode_id(inode: &Arc<dyn INode>) -> Result<INodeID> {
+    let metadata = inode.metadata()?;
+    if metadata.type_ != FileType::Dir {
+        return Err(FsError::NotDir);
+    }
+    let fs = inode.fs();
+    let fs_address = &*fs as *const dyn FileSystem as *const u8 as usize;
+    Ok((fs_address, metadata.inode))
+}
@@ -0,0 +1,150 @@
+//! 匿名管道 [`Pipe`]
+
+use super::*;
+use alloc::collections::VecDeque;
+
+/// 管道两端共享的缓冲区
+struct PipeBuffer {
+    /// 环形缓冲区，从后插入，从前弹出，长度不超过 [`PIPE_BUFFER_SIZE`]
+    data: VecDeque<u8>,
+    /// 尚未关闭的读端数量
+    readers: usize,
+    /// 尚未关闭的写端数量
+    writers: usize,
+}
+
+/// 管道两端共享的部分
+struct PipeInner {
+    /// 缓冲区
+    buffer: Mutex<PipeBuffer>,
+    /// 缓冲区为空时，读取的线程在此休眠
+    readable: Condvar,
+    /// 缓冲区已满时，写入的线程在此休眠
+    writable: Condvar,
+}
+
+/// 管道的一端，实现 [`INode`] 接口
+///
+/// 读端只能读取，写端只能写入。所有写端都关闭后，读取完剩余数据就会得到 0（文件结束）；
+/// 所有读端都关闭后，写入会失败
+pub struct Pipe {
+    /// 两端共享的部分
+    inner: Arc<PipeInner>,
+    /// 是否为写端
+    is_writer: bool,
+}
+
+impl Pipe {
+    /// 创建一个管道，返回读端和写端
+    pub fn new() -> (Arc<Pipe>, Arc<Pipe>) {
+        let inner = Arc::new(PipeInner {
+            buffer: Mutex::new(PipeBuffer {
+                data: VecDeque::with_capacity(PIPE_BUFFER_SIZE),
+                readers: 1,
+                writers: 1,
+            }),
+            readable: Condvar::default(),
+            writable: Condvar::default(),
+        });
+        let reader = Arc::new(Pipe {
+            inner: inner.clone(),
+            is_writer: false,
+        });
+        let writer = Arc::new(Pipe {
+            inner,
+            is_writer: true,
+        });
+        (reader, writer)
+    }
+}
+
+impl INode for Pipe {
+    /// Read bytes at `offset` into `buf`, return the number of bytes read.
+    ///
+    /// 缓冲区没有数据时，当前线程会休眠直到有数据写入，或者所有写端都已关闭
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> Result<usize> {
+        if offset != 0 || self.is_writer {
+            return Err(FsError::NotSupported);
+        }
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        loop {
+            let mut buffer = self.inner.buffer.lock();
+            if buffer.data.is_empty() {
+                if buffer.writers == 0 {
+                    // 文件结束
+                    return Ok(0);
+                }
+                // 等待写入（登记为等待者之后才释放锁）
+                self.inner.readable.wait(buffer);
+                continue;
+            }
+            let size = buf.len().min(buffer.data.len());
+            for (byte, b) in buf.iter_mut().zip(buffer.data.drain(..size)) {
+                *byte = b;
+            }
+            drop(buffer);
+            self.inner.writable.notify_all();
+            return Ok(size);
+        }
+    }
+
+    /// Write bytes at `offset` from `buf`, return the number of bytes written.
+    ///
+    /// 缓冲区已满时，当前线程会休眠直到数据被读出，全部写入后才返回。所有读端都已关闭时返回错误
+    fn write_at(&self, offset: usize, buf: &[u8]) -> Result<usize> {
+        if offset != 0 || !self.is_writer {
+            return Err(FsError::NotSupported);
+        }
+        let mut written = 0;
+        while written < buf.len() {
+            let mut buffer = self.inner.buffer.lock();
+            if buffer.readers == 0 {
+                return Err(FsError::NoDevice);
+            }
+            let size = (buf.len() - written).min(PIPE_BUFFER_SIZE - buffer.data.len());
+            if size == 0 {
+                // 等待读出（登记为等待者之后才释放锁）
+                self.inner.writable.wait(buffer);
+                continue;
+            }
+            buffer.data.extend(&buf[written..written + size]);
+            written += size;
+            drop(buffer);
+            self.inner.readable.notify_all();
+        }
+        Ok(written)
+    }
+
+    fn poll(&self) -> Result<PollStatus> {
+        let buffer = self.inner.buffer.lock();
+        Ok(PollStatus {
+            read: !self.is_writer && (!buffer.data.is_empty() || buffer.writers == 0),
+            write: self.is_writer && (buffer.data.len() < PIPE_BUFFER_SIZE || buffer.readers == 0),
+            error: false,
+        })
+    }
+
+    /// This is used to implement dynamics cast.
+    /// Simply return self in the implement of the function.
+    fn as_any_ref(&self) -> &dyn Any {
+        self
+    }
+}
+
+/// 关闭一端时唤醒另一端等待的线程，使其得知文件结束或写入失败
+impl Drop for Pipe {
+    fn drop(&mut self) {
+        let mut buffer = self.inner.buffer.lock();
+        if self.is_writer {
+            buffer.writers -= 1;
+            drop(buffer);
+            self.inner.readable.notify_all();
+        } else {
+            buffer.readers -= 1;
+            drop(buffer);
+            self.inner.writable.notify_all();
+        }
+    }
+}
@@ -0,0 +1,103 @@
+//! 传递给用户程序的文件信息 [`Stat`] 和目录项 [`DirEntry`]
+//!
+//! 两者的内存布局是固定的（`#[repr(C)]`），`user_lib` 中有相同的定义，修改时需要同步
+
+use super::*;
+
+/// 目录项中文件名的最大长度
+pub const NAME_MAX: usize = 256;
+
+/// 普通文件
+pub const TYPE_FILE: u32 = 1;
+/// 目录
+pub const TYPE_DIR: u32 = 2;
+/// 符号链接
+pub const TYPE_SYMLINK: u32 = 3;
+/// 字符设备
+pub const TYPE_CHAR_DEVICE: u32 = 4;
+/// 块设备
+pub const TYPE_BLOCK_DEVICE: u32 = 5;
+/// 命名管道
+pub const TYPE_NAMED_PIPE: u32 = 6;
+/// 套接字
+pub const TYPE_SOCKET: u32 = 7;
+
+/// 文件信息，由 `sys_fstat` 返回
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Stat {
+    /// 所在设备的编号
+    pub dev: u64,
+    /// INode 编号
+    pub inode: u64,
+    /// 文件类型，为 `TYPE_` 开头的常量
+    pub type_: u32,
+    /// 权限
+    pub mode: u32,
+    /// 硬链接数
+    pub nlinks: u64,
+    /// 文件大小（字节）
+    pub size: u64,
+    /// 块大小
+    pub blk_size: u64,
+    /// 占用的块数
+    pub blocks: u64,
+}
+
+/// 目录项，由 `sys_getdents` 返回
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct DirEntry {
+    /// INode 编号
+    pub inode: u64,
+    /// 文件类型，为 `TYPE_` 开头的常量
+    pub type_: u32,
+    /// 文件名的长度
+    pub name_len: u32,
+    /// 文件名，只有前 `name_len` 个字节有效
+    pub name: [u8; NAME_MAX],
+}
+
+impl From<Metadata> for Stat {
+    fn from(metadata: Metadata) -> Self {
+        Self {
+            dev: metadata.dev as u64,
+            inode: metadata.inode as u64,
+            type_: file_type(metadata.type_),
+            mode: metadata.mode as u32,
+            nlinks: metadata.nlinks as u64,
+            size: metadata.size as u64,
+            blk_size: metadata.blk_size as u64,
+            blocks: metadata.blocks as u64,
+        }
+    }
+}
+
+impl DirEntry {
+    /// 由文件名和文件信息生成目录项，过长的文件名会被截断
+    pub fn new(name: &str, metadata: &Metadata) -> Self {
+        let mut entry = Self {
+            inode: metadata.inode as u64,
+            type_: file_type(metadata.type_),
+            name_len: 0,
+            name: [0; NAME_MAX],
+        };
+        let length = name.len().min(NAME_MAX);
+        entry.name[..length].copy_from_slice(&name.as_bytes()[..length]);
+        entry.name_len = length as u32;
+        entry
+    }
+}
+
+/// 将 [`FileType`] 转换为 `TYPE_` 开头的常量
+fn file_type(type_: FileType) -> u32 {
+    match type_ {
+        FileType::File => TYPE_FILE,
+        FileType::Dir => TYPE_DIR,
+        FileType::SymLink => TYPE_SYMLINK,
+        FileType::CharDevice => TYPE_CHAR_DEVICE,
+        FileType::BlockDevice => TYPE_BLOCK_DEVICE,
+        FileType::NamedPipe => TYPE_NAMED_PIPE,
+        FileType::Socket => TYPE_SOCKET,
+    }
+}
@@ -18,16 +18,20 @@ pub struct Stdin {
 
 impl INode for Stdin {
     /// Read bytes at `offset` into `buf`, return the number of bytes read.
+    ///
+    /// 缓冲区没有数据时，当前线程会休眠直到有输入
     fn read_at(&self, offset: usize, buf: &mut [u8]) -> Result<usize> {
         if offset != 0 {
             // 不支持 offset
-            Err(FsError::NotSupported)
-        } else if self.buffer.lock().len() == 0 {
-            // 缓冲区没有数据，将当前线程休眠
-            self.condvar.wait();
-            Ok(0)
-        } else {
+            return Err(FsError::NotSupported);
+        }
+        loop {
             let mut stdin_buffer = self.buffer.lock();
+            if stdin_buffer.is_empty() {
+                // 缓冲区没有数据，将当前线程休眠（登记为等待者之后才释放锁）
+                self.condvar.wait(stdin_buffer);
+                continue;
+            }
             for (i, byte) in buf.iter_mut().enumerate() {
                 if let Some(b) = stdin_buffer.pop_front() {
                     *byte = b;
@@ -35,7 +39,7 @@ impl INode for Stdin {
                     return Ok(i);
                 }
             }
-            Ok(buf.len())
+            return Ok(buf.len());
         }
     }
 
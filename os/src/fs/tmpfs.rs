@@ -0,0 +1,394 @@
+//! 内存中的文件系统 [`TmpFileSystem`]
+//!
+//! 所有内容都保存在内存中，关机后丢失。可以挂载在 `/tmp`，也在没有块设备时作为根文件系统
+
+use super::*;
+use crate::memory::{KERNEL_HEAP_SIZE, PAGE_SIZE};
+use alloc::{collections::BTreeMap, sync::Weak};
+use core::sync::atomic::AtomicUsize;
+use spin::RwLock;
+
+/// 单个文件的最大长度。文件内容保存在内核堆中，超过时返回 [`FsError::NoDeviceSpace`]，以免耗尽内核堆
+const MAX_FILE_SIZE: usize = KERNEL_HEAP_SIZE / 4;
+
+/// 内存中的文件系统
+pub struct TmpFileSystem {
+    /// 根目录，创建文件系统之后立即设置
+    root: RwLock<Option<Arc<TmpINode>>>,
+    /// 下一个 INode 的编号
+    next_id: AtomicUsize,
+    /// 移动文件时持有，避免并发的移动把目录移动到它自己之中
+    move_lock: Mutex<()>,
+}
+
+/// 内存中的文件或目录
+pub struct TmpINode {
+    /// INode 编号
+    id: usize,
+    /// 所属的文件系统
+    fs: Weak<TmpFileSystem>,
+    /// 可变的部分
+    inner: RwLock<TmpINodeInner>,
+}
+
+/// [`TmpINode`] 中可变的部分
+struct TmpINodeInner {
+    /// 指向自己，用于查找 `.` 以及作为子目录的上级目录
+    this: Weak<TmpINode>,
+    /// 上级目录，根目录的上级目录是自己
+    parent: Weak<TmpINode>,
+    /// 权限
+    mode: u16,
+    /// 文件内容或目录项
+    content: Content,
+}
+
+/// 文件内容或目录项
+enum Content {
+    /// 文件的数据
+    File(Vec<u8>),
+    /// 目录中的文件，不含 `.` 和 `..`
+    Dir(BTreeMap<String, Arc<TmpINode>>),
+}
+
+impl TmpFileSystem {
+    /// 创建一个只有根目录的文件系统
+    pub fn new() -> Arc<Self> {
+        let fs = Arc::new(Self {
+            root: RwLock::new(None),
+            next_id: AtomicUsize::new(1),
+            move_lock: Mutex::new(()),
+        });
+        let root = fs.new_inode(FileType::Dir, 0o777, None);
+        *fs.root.write() = Some(root);
+        fs
+    }
+
+    /// 创建一个 INode，`parent` 为 `None` 时上级目录为自己
+    fn new_inode(
+        self: &Arc<Self>,
+        type_: FileType,
+        mode: u16,
+        parent: Option<&Arc<TmpINode>>,
+    ) -> Arc<TmpINode> {
+        let content = match type_ {
+            FileType::Dir => Content::Dir(BTreeMap::new()),
+            _ => Content::File(Vec::new()),
+        };
+        let inode = Arc::new(TmpINode {
+            id: self.next_id.fetch_add(1, Ordering::Relaxed),
+            fs: Arc::downgrade(self),
+            inner: RwLock::new(TmpINodeInner {
+                this: Weak::new(),
+                parent: Weak::new(),
+                mode,
+                content,
+            }),
+        });
+        let mut inner = inode.inner.write();
+        inner.this = Arc::downgrade(&inode);
+        inner.parent = Arc::downgrade(parent.unwrap_or(&inode));
+        drop(inner);
+        inode
+    }
+}
+
+impl FileSystem for TmpFileSystem {
+    /// 内容都在内存中，不需要写回
+    fn sync(&self) -> Result<()> {
+        Ok(())
+    }
+
+    fn root_inode(&self) -> Arc<dyn INode> {
+        self.root.read().clone().unwrap()
+    }
+
+    fn info(&self) -> FsInfo {
+        FsInfo {
+            bsize: PAGE_SIZE,
+            frsize: PAGE_SIZE,
+            blocks: 0,
+            bfree: 0,
+            bavail: 0,
+            files: self.next_id.load(Ordering::Relaxed),
+            ffree: 0,
+            namemax: NAME_MAX,
+        }
+    }
+}
+
+impl TmpINode {
+    /// 文件系统，在文件系统被释放后不会再被使用
+    fn tmpfs(&self) -> Arc<TmpFileSystem> {
+        self.fs.upgrade().unwrap()
+    }
+
+    /// `ancestor` 是否为自己或者自己的上级目录
+    fn is_descendant_of(&self, ancestor: &TmpINode) -> bool {
+        let mut current = self.inner.read().this.upgrade().unwrap();
+        loop {
+            if current.id == ancestor.id {
+                return true;
+            }
+            // 上级目录已经被删除，则已经不在文件系统的目录树中
+            let parent = match current.inner.read().parent.upgrade() {
+                Some(parent) => parent,
+                None => return false,
+            };
+            if parent.id == current.id {
+                return false;
+            }
+            current = parent;
+        }
+    }
+}
+
+/// 将文件内容的长度改为 `len`，扩大的部分填充 0，超过 [`MAX_FILE_SIZE`] 时返回错误
+fn resize_data(data: &mut Vec<u8>, len: usize) -> Result<()> {
+    if len > MAX_FILE_SIZE {
+        return Err(FsError::NoDeviceSpace);
+    }
+    if len > data.len() {
+        // 按需要的长度分配，而不是成倍扩大
+        data.reserve_exact(len - data.len());
+    }
+    data.resize(len, 0);
+    Ok(())
+}
+
+/// 检查文件名，不能为空，不能含有 `/`，也不能是 `.` 或 `..`
+fn check_name(name: &str) -> Result<()> {
+    if name.is_empty() || name.len() > NAME_MAX || name.contains('/') || name == "." || name == ".."
+    {
+        return Err(FsError::InvalidParam);
+    }
+    Ok(())
+}
+
+impl INode for TmpINode {
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> Result<usize> {
+        match &self.inner.read().content {
+            Content::File(data) => {
+                if offset >= data.len() {
+                    return Ok(0);
+                }
+                let size = buf.len().min(data.len() - offset);
+                buf[..size].copy_from_slice(&data[offset..offset + size]);
+                Ok(size)
+            }
+            Content::Dir(_) => Err(FsError::NotFile),
+        }
+    }
+
+    fn write_at(&self, offset: usize, buf: &[u8]) -> Result<usize> {
+        match &mut self.inner.write().content {
+            Content::File(data) => {
+                let end = offset
+                    .checked_add(buf.len())
+                    .ok_or(FsError::NoDeviceSpace)?;
+                if data.len() < end {
+                    resize_data(data, end)?;
+                }
+                data[offset..end].copy_from_slice(buf);
+                Ok(buf.len())
+            }
+            Content::Dir(_) => Err(FsError::NotFile),
+        }
+    }
+
+    fn poll(&self) -> Result<PollStatus> {
+        Ok(PollStatus {
+            read: true,
+            write: true,
+            error: false,
+        })
+    }
+
+    fn metadata(&self) -> Result<Metadata> {
+        let inner = self.inner.read();
+        let (type_, size, nlinks) = match &inner.content {
+            Content::File(data) => (FileType::File, data.len(), 1),
+            Content::Dir(entries) => (FileType::Dir, entries.len() + 2, 2),
+        };
+        let time = Timespec { sec: 0, nsec: 0 };
+        Ok(Metadata {
+            dev: 0,
+            inode: self.id,
+            size,
+            blk_size: PAGE_SIZE,
+            blocks: (size + PAGE_SIZE - 1) / PAGE_SIZE,
+            atime: time,
+            mtime: time,
+            ctime: time,
+            type_,
+            mode: inner.mode,
+            nlinks,
+            uid: 0,
+            gid: 0,
+            rdev: 0,
+        })
+    }
+
+    /// 内容都在内存中，不需要写回
+    fn sync_all(&self) -> Result<()> {
+        Ok(())
+    }
+
+    /// 内容都在内存中，不需要写回
+    fn sync_data(&self) -> Result<()> {
+        Ok(())
+    }
+
+    /// 改变文件大小，扩大的部分填充 0
+    fn resize(&self, len: usize) -> Result<()> {
+        match &mut self.inner.write().content {
+            Content::File(data) => resize_data(data, len),
+            Content::Dir(_) => Err(FsError::NotFile),
+        }
+    }
+
+    /// 在目录中创建文件或目录，不支持其他类型
+    fn create(&self, name: &str, type_: FileType, mode: u32) -> Result<Arc<dyn INode>> {
+        check_name(name)?;
+        if type_ != FileType::File && type_ != FileType::Dir {
+            return Err(FsError::NotSupported);
+        }
+        let mut inner = self.inner.write();
+        let this = inner.this.upgrade().unwrap();
+        let entries = match &mut inner.content {
+            Content::Dir(entries) => entries,
+            Content::File(_) => return Err(FsError::NotDir),
+        };
+        if entries.contains_key(name) {
+            return Err(FsError::EntryExist);
+        }
+        let inode = self.tmpfs().new_inode(type_, mode as u16, Some(&this));
+        entries.insert(String::from(name), inode.clone());
+        Ok(inode)
+    }
+
+    /// 删除文件或空目录
+    fn unlink(&self, name: &str) -> Result<()> {
+        check_name(name)?;
+        let mut inner = self.inner.write();
+        let entries = match &mut inner.content {
+            Content::Dir(entries) => entries,
+            Content::File(_) => return Err(FsError::NotDir),
+        };
+        let inode = entries.get(name).ok_or(FsError::EntryNotFound)?;
+        if let Content::Dir(children) = &inode.inner.read().content {
+            if !children.is_empty() {
+                return Err(FsError::DirNotEmpty);
+            }
+        }
+        entries.remove(name);
+        Ok(())
+    }
+
+    /// 将目录中的 `old_name` 移动到目录 `target` 中，改名为 `new_name`
+    ///
+    /// `target` 必须在同一个文件系统中，`new_name` 不能已经存在，目录不能移动到自己之中
+    fn move_(&self, old_name: &str, target: &Arc<dyn INode>, new_name: &str) -> Result<()> {
+        check_name(old_name)?;
+        check_name(new_name)?;
+        let target = target
+            .as_any_ref()
+            .downcast_ref::<TmpINode>()
+            .ok_or(FsError::NotSameFs)?;
+        if !Weak::ptr_eq(&self.fs, &target.fs) {
+            return Err(FsError::NotSameFs);
+        }
+        let fs = self.tmpfs();
+        let _move_lock = fs.move_lock.lock();
+        let inode = self.find_entry(old_name)?;
+        if target.is_descendant_of(&inode) {
+            return Err(FsError::InvalidParam);
+        }
+        if self.id == target.id {
+            let mut inner = self.inner.write();
+            if let Content::Dir(entries) = &mut inner.content {
+                if entries.contains_key(new_name) {
+                    return Err(FsError::EntryExist);
+                }
+                entries.remove(old_name);
+                entries.insert(String::from(new_name), inode);
+            }
+            return Ok(());
+        }
+        match &target.inner.read().content {
+            Content::Dir(entries) if entries.contains_key(new_name) => {
+                return Err(FsError::EntryExist)
+            }
+            Content::Dir(_) => {}
+            Content::File(_) => return Err(FsError::NotDir),
+        }
+        // 同时只持有一个目录的锁：删除目录项时会在持有上级目录的锁时读取下级目录
+        if let Content::Dir(entries) = &mut self.inner.write().content {
+            entries.remove(old_name);
+        }
+        let mut target_inner = target.inner.write();
+        let target_this = target_inner.this.clone();
+        if let Content::Dir(entries) = &mut target_inner.content {
+            if !entries.contains_key(new_name) {
+                inode.inner.write().parent = target_this;
+                entries.insert(String::from(new_name), inode);
+                return Ok(());
+            }
+        }
+        // 检查之后目标目录中又创建了同名文件，放回原处
+        drop(target_inner);
+        if let Content::Dir(entries) = &mut self.inner.write().content {
+            entries.insert(String::from(old_name), inode);
+        }
+        Err(FsError::EntryExist)
+    }
+
+    fn find(&self, name: &str) -> Result<Arc<dyn INode>> {
+        let inner = self.inner.read();
+        match name {
+            "." => Ok(inner.this.upgrade().unwrap()),
+            // 上级目录可能已经被删除（例如工作目录所在的目录树被删除）
+            ".." => Ok(inner.parent.upgrade().ok_or(FsError::EntryNotFound)?),
+            _ => {
+                drop(inner);
+                Ok(self.find_entry(name)?)
+            }
+        }
+    }
+
+    /// 第 0、1 项为 `.` 和 `..`，之后按文件名排序
+    fn get_entry(&self, id: usize) -> Result<String> {
+        match &self.inner.read().content {
+            Content::Dir(entries) => match id {
+                0 => Ok(String::from(".")),
+                1 => Ok(String::from("..")),
+                _ => entries
+                    .keys()
+                    .nth(id - 2)
+                    .cloned()
+                    .ok_or(FsError::EntryNotFound),
+            },
+            Content::File(_) => Err(FsError::NotDir),
+        }
+    }
+
+    fn fs(&self) -> Arc<dyn FileSystem> {
+        self.tmpfs()
+    }
+
+    /// This is used to implement dynamics cast.
+    /// Simply return self in the implement of the function.
+    fn as_any_ref(&self) -> &dyn Any {
+        self
+    }
+}
+
+impl TmpINode {
+    /// 在目录中查找文件，不包括 `.` 和 `..`
+    fn find_entry(&self, name: &str) -> Result<Arc<TmpINode>> {
+        match &self.inner.read().content {
+            Content::Dir(entries) => entries.get(name).cloned().ok_or(FsError::EntryNotFound),
+            Content::File(_) => Err(FsError::NotDir),
+        }
+    }
+}
@@ -0,0 +1,83 @@
+//! 多核支持：hart 编号、启动其他 hart 以及核间中断
+//!
+//! 每个 hart 将自己的编号保存在 `tp` 寄存器中（见 `entry.asm`）。进入用户态时，`tp` 会被暂存在内核栈顶，
+//! 中断时再取回（见 `interrupt.asm`），因此内核中可以随时通过 [`hart_id`] 得到当前 hart 的编号。
+
+use crate::memory::{PhysicalAddress, VirtualAddress};
+use crate::sbi;
+use core::sync::atomic::{spin_loop_hint, AtomicBool, AtomicUsize, Ordering};
+
+/// 支持的最多 hart 数量，需要与 `entry.asm` 中启动栈的数量一致
+pub const MAX_HART_COUNT: usize = 8;
+
+/// 是否已经有 hart 负责启动（第一个到达 `rust_main` 的 hart）
+static BOOT_CLAIMED: AtomicBool = AtomicBool::new(false);
+/// 负责启动的 hart 是否已经完成初始化
+static BOOT_FINISHED: AtomicBool = AtomicBool::new(false);
+/// 已经开始运行调度循环的 hart
+static RUNNING_HARTS: AtomicUsize = AtomicUsize::new(0);
+/// 正在等待中断的空闲 hart
+static IDLE_HARTS: AtomicUsize = AtomicUsize::new(0);
+
+/// 当前 hart 的编号
+pub fn hart_id() -> usize {
+    let id;
+    unsafe { llvm_asm!("mv $0, tp" : "=r"(id) ::: "volatile") };
+    id
+}
+
+/// 尝试成为负责启动的 hart，只有第一个调用者返回 `true`
+pub fn claim_boot() -> bool {
+    !BOOT_CLAIMED.swap(true, Ordering::AcqRel)
+}
+
+/// 负责启动的 hart 完成初始化后调用，通过 SBI 启动其他 hart
+///
+/// 有的 SBI 实现会让所有 hart 同时进入内核，此时其他 hart 在 [`wait_for_boot`] 中等待，
+/// 而这里的启动请求会失败并被忽略
+pub fn finish_boot(dtb_pa: PhysicalAddress) {
+    extern "C" {
+        /// `entry.asm` 中的程序入口
+        fn _start();
+    }
+    BOOT_FINISHED.store(true, Ordering::Release);
+    // 其他 hart 在关闭分页的状态下启动，因此需要入口的物理地址
+    let start = PhysicalAddress::from(VirtualAddress(_start as usize));
+    for id in (0..MAX_HART_COUNT).filter(|&id| id != hart_id()) {
+        sbi::hart_start(id, start.0, dtb_pa.0);
+    }
+}
+
+/// 等待负责启动的 hart 完成初始化
+pub fn wait_for_boot() {
+    while !BOOT_FINISHED.load(Ordering::Acquire) {
+        spin_loop_hint();
+    }
+}
+
+/// 标记当前 hart 开始运行调度循环，此后它会参与 TLB 的刷新
+pub fn set_running() {
+    RUNNING_HARTS.fetch_or(1 << hart_id(), Ordering::AcqRel);
+}
+
+/// 除当前 hart 以外，所有正在运行的 hart
+pub fn other_harts() -> usize {
+    RUNNING_HARTS.load(Ordering::Acquire) & !(1 << hart_id())
+}
+
+/// 标记当前 hart 是否空闲（正在等待中断）
+pub fn set_idle(idle: bool) {
+    if idle {
+        IDLE_HARTS.fetch_or(1 << hart_id(), Ordering::AcqRel);
+    } else {
+        IDLE_HARTS.fetch_and(!(1 << hart_id()), Ordering::AcqRel);
+    }
+}
+
+/// 用核间中断唤醒其他空闲的 hart，例如有新的线程可以执行时
+pub fn wake_idle_harts() {
+    let idle = IDLE_HARTS.load(Ordering::Acquire) & !(1 << hart_id());
+    if idle != 0 {
+        sbi::send_ipi(idle);
+    }
+}
@@ -1,10 +1,11 @@
 use super::context::Context;
 use super::timer;
 use crate::fs::STDIN;
+use crate::hart::hart_id;
 use crate::kernel::syscall_handler;
 use crate::memory::*;
-use crate::process::PROCESSOR;
-use crate::sbi::console_getchar;
+use crate::process::{exit_current_thread, preempt_current_thread, PROCESSOR};
+use crate::sbi::{clear_ipi, console_getchar};
 use riscv::register::{
     scause::{Exception, Interrupt, Scause, Trap},
     sie, stvec,
@@ -12,9 +13,9 @@ use riscv::register::{
 
 global_asm!(include_str!("./interrupt.asm"));
 
-/// 初始化中断处理
+/// 初始化当前 hart 的中断处理
 ///
-/// 把中断入口 `__interrupt` 写入 `stvec` 中，并且开启中断使能
+/// 把中断入口 `__interrupt` 写入 `stvec` 中，并且开启核间中断使能
 pub fn init() {
     unsafe {
         extern "C" {
@@ -23,18 +24,29 @@ pub fn init() {
         }
         // 使用 Direct 模式，将中断入口设置为 `__interrupt`
         stvec::write(__interrupt as usize, stvec::TrapMode::Direct);
+        // 目前处于内核中，sscratch 置为 0（见 `interrupt.asm`）
+        llvm_asm!("csrw sscratch, zero" :::: "volatile");
+        // 开启核间中断使能，用于唤醒空闲的 hart
+        sie::set_ssoft();
+    }
+}
 
+/// 初始化外部中断，只在负责启动的 hart 上调用，外部中断都由它处理
+pub fn init_external() {
+    unsafe {
         // 开启外部中断使能
         sie::set_sext();
 
+        // 当前 hart 的 S 态在 PLIC 中对应的 context 编号
+        let context = 2 * hart_id() + 1;
         // 在 OpenSBI 中开启外部中断
-        *PhysicalAddress(0x0c00_2080).deref_kernel() = 1u32 << 10;
+        *PhysicalAddress(0x0c00_2000 + 0x80 * context).deref_kernel() = 1u32 << 10;
         // 在 OpenSBI 中开启串口
         *PhysicalAddress(0x1000_0004).deref_kernel() = 0x0bu8;
         *PhysicalAddress(0x1000_0001).deref_kernel() = 0x01u8;
         // 其他一些外部中断相关魔数
         *PhysicalAddress(0x0C00_0028).deref_kernel() = 0x07u32;
-        *PhysicalAddress(0x0C20_1000).deref_kernel() = 0u32;
+        *PhysicalAddress(0x0C20_0000 + 0x1000 * context).deref_kernel() = 0u32;
     }
 }
 
@@ -43,50 +55,100 @@ pub fn init() {
 /// `interrupt.asm` 首先保存寄存器至 Context，其作为参数和 scause 以及 stval 一并传入此函数
 /// 具体的中断类型需要根据 scause 来推断，然后分别处理
 #[no_mangle]
-pub fn handle_interrupt(context: &mut Context, scause: Scause, stval: usize) -> *mut Context {
+pub fn handle_interrupt(context: &mut Context, scause: Scause, stval: usize) {
     // 首先检查线程是否已经结束（内核线程会自己设置标记来结束自己）
     {
-        let mut processor = PROCESSOR.lock();
-        let current_thread = processor.current_thread();
+        let current_thread = PROCESSOR.lock().current_thread();
         if current_thread.as_ref().inner().dead {
             println!("thread {} exit", current_thread.id);
-            processor.kill_current_thread();
-            return processor.prepare_next_thread();
+            drop(current_thread);
+            exit_current_thread();
         }
     }
-    // 根据中断类型来处理，返回的 Context 必须位于放在内核栈顶
+    // 根据中断类型来处理，处理完成后从内核栈顶的 Context 返回
     match scause.cause() {
         // 断点中断（ebreak）
         Trap::Exception(Exception::Breakpoint) => breakpoint(context),
         // 系统调用
         Trap::Exception(Exception::UserEnvCall) => syscall_handler(context),
         // 时钟中断
-        Trap::Interrupt(Interrupt::SupervisorTimer) => supervisor_timer(context),
+        Trap::Interrupt(Interrupt::SupervisorTimer) => supervisor_timer(),
         // 外部中断（键盘输入）
-        Trap::Interrupt(Interrupt::SupervisorExternal) => supervisor_external(context),
+        Trap::Interrupt(Interrupt::SupervisorExternal) => supervisor_external(),
+        // 核间中断
+        Trap::Interrupt(Interrupt::SupervisorSoft) => supervisor_soft(),
+        // 缺页（按需分配或写时复制）
+        Trap::Exception(Exception::LoadPageFault)
+        | Trap::Exception(Exception::StorePageFault)
+        | Trap::Exception(Exception::InstructionPageFault) => page_fault(scause, stval),
         // 其他情况，无法处理
         _ => fault("unimplemented interrupt type", scause, stval),
     }
 }
 
+/// 内核中（中断处理流程或调度循环中）再次发生中断时的处理入口
+///
+/// 中断处理流程中是关闭中断的，所以这里只会遇到异常。例如系统调用访问用户内存时，
+/// 遇到尚未分配或写时复制的页面而发生缺页。调度循环等待时会短暂开启中断，因此也会遇到时钟和外部中断。
+/// 此时不能切换线程，处理完成后直接回到原来的位置继续执行。
+///
+/// 系统调用访问用户内存时发生无法处理的缺页，说明用户传入了非法的地址，此时终止用户进程而不是 panic。
+/// 系统调用一般会事先检查用户的缓冲区，这里只是最后的防线：被终止的系统调用所持有的锁不会被释放
+#[no_mangle]
+pub fn handle_kernel_interrupt(context: &mut Context, scause: Scause, stval: usize) {
+    match scause.cause() {
+        Trap::Interrupt(Interrupt::SupervisorTimer) => {
+            timer::tick();
+            return;
+        }
+        Trap::Interrupt(Interrupt::SupervisorExternal) => {
+            supervisor_external();
+            return;
+        }
+        Trap::Interrupt(Interrupt::SupervisorSoft) => {
+            supervisor_soft();
+            return;
+        }
+        _ => {}
+    }
+    if let Some(access) = page_fault_access(scause) {
+        match handle_page_fault(stval, access) {
+            Ok(()) => return,
+            Err(msg) if stval < USER_END_ADDRESS && current_is_user() => fault(msg, scause, stval),
+            Err(_) => {}
+        }
+    }
+    panic!(
+        "unresolved interrupt in kernel: {:?}, stval: {:x}, sepc: {:x}",
+        scause.cause(),
+        stval,
+        context.sepc
+    );
+}
+
 /// 处理 ebreak 断点
 ///
 /// 继续执行，其中 `sepc` 增加 2 字节，以跳过当前这条 `ebreak` 指令
-fn breakpoint(context: &mut Context) -> *mut Context {
+fn breakpoint(context: &mut Context) {
     println!("Breakpoint at 0x{:x}", context.sepc);
     context.sepc += 2;
-    context
 }
 
-/// 处理时钟中断
-fn supervisor_timer(context: &mut Context) -> *mut Context {
+/// 处理时钟中断，切换到下一个线程
+fn supervisor_timer() {
     timer::tick();
-    PROCESSOR.lock().park_current_thread(context);
-    PROCESSOR.lock().prepare_next_thread()
+    preempt_current_thread();
+}
+
+/// 处理核间中断
+///
+/// 目前只用于唤醒等待中断的空闲 hart，清除即可
+fn supervisor_soft() {
+    clear_ipi();
 }
 
 /// 处理外部中断，只实现了键盘输入
-fn supervisor_external(context: &mut Context) -> *mut Context {
+fn supervisor_external() {
     let mut c = console_getchar();
     if c <= 255 {
         if c == '\r' as usize {
@@ -94,19 +156,54 @@ fn supervisor_external(context: &mut Context) -> *mut Context {
         }
         STDIN.push(c as u8);
     }
-    context
+}
+
+/// 处理缺页异常
+///
+/// 交由当前进程的 [`MemorySet`] 处理，无法处理时终止线程
+fn page_fault(scause: Scause, stval: usize) {
+    let access = page_fault_access(scause).unwrap();
+    if let Err(msg) = handle_page_fault(stval, access) {
+        fault(msg, scause, stval);
+    }
+}
+
+/// 缺页异常的访问所需的权限，不是缺页异常时返回 `None`
+fn page_fault_access(scause: Scause) -> Option<Flags> {
+    match scause.cause() {
+        Trap::Exception(Exception::LoadPageFault) => Some(Flags::READABLE),
+        Trap::Exception(Exception::StorePageFault) => Some(Flags::WRITABLE),
+        Trap::Exception(Exception::InstructionPageFault) => Some(Flags::EXECUTABLE),
+        _ => None,
+    }
+}
+
+/// 让当前进程的 [`MemorySet`] 处理发生在 `stval` 的缺页
+fn handle_page_fault(stval: usize, access: Flags) -> MemoryResult<()> {
+    let process = PROCESSOR.lock().current_thread().process.clone();
+    let result = process
+        .inner()
+        .memory_set
+        .handle_page_fault(VirtualAddress(stval), access);
+    result
+}
+
+/// 当前线程是否属于用户进程
+fn current_is_user() -> bool {
+    PROCESSOR.lock().current_thread().process.is_user
 }
 
 /// 出现未能解决的异常，终止当前线程
-fn fault(msg: &str, scause: Scause, stval: usize) -> *mut Context {
-    println!(
-        "{:#x?} terminated: {}",
-        PROCESSOR.lock().current_thread(),
-        msg
-    );
+///
+/// 如果是用户进程，则整个进程以退出码 -1 结束
+fn fault(msg: &str, scause: Scause, stval: usize) -> ! {
+    let thread = PROCESSOR.lock().current_thread();
+    println!("{:#x?} terminated: {}", thread, msg);
     println!("cause: {:?}, stval: {:x}", scause.cause(), stval);
-
-    PROCESSOR.lock().kill_current_thread();
+    if thread.process.is_user {
+        thread.process.exit(-1);
+    }
+    drop(thread);
     // 跳转到 PROCESSOR 调度的下一个线程
-    PROCESSOR.lock().prepare_next_thread()
+    exit_current_thread()
 }
@@ -30,12 +30,14 @@
 # 进入中断
 # 保存 Context 并且进入 Rust 中的中断处理函数 interrupt::handler::handle_interrupt()
 __interrupt:
-    # 因为线程当前的栈不一定可用，必须切换到内核栈来保存 Context 并进行中断流程
+    # 因为线程当前的栈不一定可用，必须切换到线程的内核栈来保存 Context 并进行中断流程
     # 因此，我们使用 sscratch 寄存器保存内核栈地址
     # 思考：sscratch 的值最初是在什么地方写入的？
 
     # 交换 sp 和 sscratch（切换到内核栈）
     csrrw   sp, sscratch, sp
+    # 在中断处理流程中 sscratch 为 0，此时发生的是内核中的嵌套中断，不需要切换栈
+    beqz    sp, __interrupt_nested
     # 在内核栈开辟 Context 的空间
     addi    sp, sp, -CONTEXT_SIZE * REG_SIZE
     
@@ -44,6 +46,8 @@ __interrupt:
     # 将本来的栈地址 sp（即 x2）保存
     csrr    x1, sscratch
     SAVE    x1, 2
+    # 中断处理流程中将 sscratch 置为 0，用来识别嵌套的中断
+    csrw    sscratch, x0
     # 保存 x3 至 x31
     .set    n, 3
     .rept   29
@@ -56,6 +60,8 @@ __interrupt:
     csrr    t1, sepc
     SAVE    t0, 32
     SAVE    t1, 33
+    # 用户程序可能改写了 tp，从内核栈顶取回当前 hart 的编号（由 __restore 写入）
+    ld      tp, CONTEXT_SIZE * REG_SIZE(sp)
     # 调用 handle_interrupt，传入参数
     # context: &mut Context
     mv      a0, sp
@@ -67,13 +73,11 @@ __interrupt:
 
     .globl __restore
 # 离开中断
-# 此时内核栈顶被推入了一个 Context，而 a0 指向它
+# 此时内核栈顶被推入了一个 Context，而 sp 指向它
 # 接下来从 Context 中恢复所有寄存器，并将 Context 出栈（用 sscratch 记录内核栈地址）
 # 最后跳转至恢复的 sepc 的位置
 __restore:
-    # 从 a0 中读取 sp
-    # 思考：a0 是在哪里被赋值的？（有两种情况）
-    mv      sp, a0
+    # 思考：sp 在什么情况下指向 Context？（有两种情况：中断处理返回，或 __switch 到一个新线程）
     # 恢复 CSR
     LOAD    t0, 32
     LOAD    t1, 33
@@ -82,6 +86,14 @@ __restore:
     # 将内核栈地址写入 sscratch
     addi    t0, sp, CONTEXT_SIZE * REG_SIZE
     csrw    sscratch, t0
+    # 在内核栈顶之上保存当前 hart 的编号，下次中断时取回
+    sd      tp, 0(t0)
+    # 返回内核线程（sstatus.SPP 为 1）时，用当前的 tp 覆盖 Context 中的值，因为线程可能换了 hart 执行
+    LOAD    t0, 32
+    andi    t0, t0, 1 << 8
+    beqz    t0, 1f
+    SAVE    tp, 4
+1:
 
     # 恢复通用寄存器
     LOAD    x1, 1
@@ -94,4 +106,52 @@ __restore:
 
     # 恢复 sp（又名 x2）这里最后恢复是为了上面可以正常使用 LOAD 宏
     LOAD    x2, 2
-    sret
\ No newline at end of file
+    sret
+
+# 内核中的嵌套中断
+# 例如系统调用访问用户内存时发生缺页，或者调度器等待时收到的时钟和外部中断。
+# 此时直接在当前的栈上保存 Context，处理完成后恢复现场并返回，sscratch 始终保持为 0
+__interrupt_nested:
+    # 此时 sp 为 0，而 sscratch 中是原本的栈地址
+    csrr    sp, sscratch
+    addi    sp, sp, -CONTEXT_SIZE * REG_SIZE
+
+    # 保存通用寄存器，除了 x0（固定为 0）
+    SAVE    x1, 1
+    # 将本来的栈地址 sp（即 x2）保存
+    csrr    x1, sscratch
+    SAVE    x1, 2
+    csrw    sscratch, x0
+    # 保存 x3 至 x31
+    .set    n, 3
+    .rept   29
+        SAVE_N  %n
+        .set    n, n + 1
+    .endr
+
+    # 取出 CSR 并保存
+    csrr    t0, sstatus
+    csrr    t1, sepc
+    SAVE    t0, 32
+    SAVE    t1, 33
+    # 调用 handle_kernel_interrupt，参数与 handle_interrupt 相同
+    mv      a0, sp
+    csrr    a1, scause
+    csrr    a2, stval
+    jal     handle_kernel_interrupt
+
+    # 恢复 CSR
+    LOAD    t0, 32
+    LOAD    t1, 33
+    csrw    sstatus, t0
+    csrw    sepc, t1
+    # 恢复通用寄存器
+    LOAD    x1, 1
+    .set    n, 3
+    .rept   29
+        LOAD_N  %n
+        .set    n, n + 1
+    .endr
+    # 最后恢复 sp
+    LOAD    x2, 2
+    sret
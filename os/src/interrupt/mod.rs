@@ -11,9 +11,17 @@ pub use context::Context;
 /// 初始化中断相关的子模块
 ///
 /// - [`handler::init`]
+/// - [`handler::init_external`]
 /// - [`timer::init`]
 pub fn init() {
     handler::init();
+    handler::init_external();
     timer::init();
     println!("mod interrupt initialized");
 }
+
+/// 初始化其他 hart 的中断，外部中断只由负责启动的 hart 处理
+pub fn init_secondary() {
+    handler::init();
+    timer::init();
+}
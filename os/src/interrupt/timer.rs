@@ -1,15 +1,17 @@
 //! 预约和处理时钟中断
 
+use crate::fs::{SYNC_CONDVAR, SYNC_INTERVAL};
 use crate::sbi::set_timer;
+use core::sync::atomic::{AtomicUsize, Ordering};
 use riscv::register::{sie, time};
 
-/// 触发时钟中断计数
-pub static mut TICKS: usize = 0;
+/// 触发时钟中断计数（所有 hart 的总和）
+pub static TICKS: AtomicUsize = AtomicUsize::new(0);
 
 /// 时钟中断的间隔，单位是 CPU 指令
 static INTERVAL: usize = 100000;
 
-/// 初始化时钟中断
+/// 初始化当前 hart 的时钟中断
 ///
 /// 开启时钟中断使能，并且预约第一次时钟中断
 pub fn init() {
@@ -30,13 +32,14 @@ fn set_next_timeout() {
 
 /// 每一次时钟中断时调用
 ///
-/// 设置下一次时钟中断，同时计数 +1
+/// 设置下一次时钟中断，同时计数 +1。每隔 [`SYNC_INTERVAL`] 次唤醒写回文件系统的线程
 pub fn tick() {
     set_next_timeout();
-    unsafe {
-        TICKS += 1;
-        // if TICKS % 100 == 0 {
-        //     println!("{} tick", TICKS);
-        // }
+    let ticks = TICKS.fetch_add(1, Ordering::Relaxed) + 1;
+    if ticks % SYNC_INTERVAL == 0 {
+        SYNC_CONDVAR.notify_one();
     }
+    // if TICKS.load(Ordering::Relaxed) % 100 == 0 {
+    //     println!("{} tick", TICKS.load(Ordering::Relaxed));
+    // }
 }
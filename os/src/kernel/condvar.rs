@@ -10,12 +10,26 @@ pub struct Condvar {
 }
 
 impl Condvar {
-    /// 令当前线程休眠，等待此条件变量
-    pub fn wait(&self) {
-        self.watchers
-            .lock()
-            .push_back(PROCESSOR.lock().current_thread());
-        PROCESSOR.lock().sleep_current_thread();
+    /// 令当前线程休眠，等待此条件变量，被唤醒后返回
+    ///
+    /// `guard` 是检查等待条件时持有的锁，在当前线程登记为等待者之后才释放。
+    /// 这样其他 hart 即使在此期间改变条件并唤醒，也不会错过当前线程。除此以外，调用时不能持有任何锁
+    pub fn wait<T>(&self, guard: T) {
+        let mut watchers = self.watchers.lock();
+        let mut processor = PROCESSOR.lock();
+        watchers.push_back(processor.current_thread());
+        processor.sleep_current_thread();
+        drop(processor);
+        drop(watchers);
+        drop(guard);
+        switch_to_scheduler();
+    }
+
+    /// 令当前的内核线程休眠，等待此条件变量，被唤醒后返回
+    ///
+    /// 内核线程并不处在中断处理流程中，通过 [`switch_in_kernel_thread`] 关闭中断之后再休眠
+    pub fn wait_kernel_thread(&self) {
+        switch_in_kernel_thread(|| self.wait(()));
     }
 
     /// 唤起一个等待此条件变量的线程
@@ -24,4 +38,13 @@ impl Condvar {
             PROCESSOR.lock().wake_thread(thread);
         }
     }
+
+    /// 唤起所有等待此条件变量的线程
+    pub fn notify_all(&self) {
+        let mut watchers = self.watchers.lock();
+        let mut processor = PROCESSOR.lock();
+        while let Some(thread) = watchers.pop_front() {
+            processor.wake_thread(thread);
+        }
+    }
 }
@@ -1,45 +1,380 @@
 //! 文件相关的内核功能
 
 use super::*;
-use core::slice::from_raw_parts_mut;
+use crate::fs::{
+    self, is_mounted, join_path, lookup_parent, DirEntry, FileHandle, FileType, FsError, INode,
+    OpenFlags, Pipe, SeekFrom, Stat,
+};
+use crate::memory::Flags;
+use core::slice::{from_raw_parts, from_raw_parts_mut};
+
+/// 从文件开头计算位置
+pub const SEEK_SET: usize = 0;
+/// 从当前位置计算位置
+pub const SEEK_CUR: usize = 1;
+/// 从文件末尾计算位置
+pub const SEEK_END: usize = 2;
+
+/// 打开 `path` 处的文件，`flags` 为 [`OpenFlags`]
+///
+/// 相对路径从当前工作目录开始查找。返回编号最小的空闲描述符；
+/// 路径不合法、文件不存在、无法按要求打开或者描述符已满时返回 -1
+pub(super) fn sys_open(path: *const u8, length: usize, flags: usize) -> SyscallResult {
+    let path = match user_string(path, length) {
+        Some(path) => path,
+        None => return SyscallResult::Proceed(-1),
+    };
+    let flags = match OpenFlags::from_bits(flags) {
+        Some(flags) => flags,
+        None => return SyscallResult::Proceed(-1),
+    };
+    match FileHandle::open(&current_cwd(), &path, flags) {
+        Ok(file) => {
+            let process = PROCESSOR.lock().current_thread().process.clone();
+            // 描述符已满时，文件在释放进程的锁之后才关闭
+            let fd = process.inner().add_descriptor(file.clone());
+            match fd {
+                Ok(fd) => SyscallResult::Proceed(fd as isize),
+                Err(_) => SyscallResult::Proceed(-1),
+            }
+        }
+        Err(_) => SyscallResult::Proceed(-1),
+    }
+}
+
+/// 关闭描述符，成功时返回 0，描述符不存在时返回 -1
+pub(super) fn sys_close(fd: usize) -> SyscallResult {
+    let process = PROCESSOR.lock().current_thread().process.clone();
+    // 文件在释放进程的锁之后才关闭
+    let file = process.inner().remove_descriptor(fd);
+    match file {
+        Some(_) => SyscallResult::Proceed(0),
+        None => SyscallResult::Proceed(-1),
+    }
+}
+
+/// 复制描述符 `fd`，返回编号最小的空闲描述符
+///
+/// 两个描述符指向同一个 [`FileHandle`]，共享读写位置。`fd` 不存在或者描述符已满时返回 -1
+pub(super) fn sys_dup(fd: usize) -> SyscallResult {
+    let process = PROCESSOR.lock().current_thread().process.clone();
+    let mut inner = process.inner();
+    match inner.descriptors.get(fd).cloned().flatten() {
+        Some(file) => match inner.add_descriptor(file) {
+            Ok(new_fd) => SyscallResult::Proceed(new_fd as isize),
+            Err(_) => SyscallResult::Proceed(-1),
+        },
+        None => SyscallResult::Proceed(-1),
+    }
+}
+
+/// 将描述符 `fd` 复制到 `new_fd`，返回 `new_fd`
+///
+/// `new_fd` 原先打开的文件会被关闭，例如复制到 0 / 1 即可重定向当前进程的输入 / 输出。
+/// `fd` 不存在或 `new_fd` 超出上限时返回 -1
+pub(super) fn sys_dup2(fd: usize, new_fd: usize) -> SyscallResult {
+    let process = PROCESSOR.lock().current_thread().process.clone();
+    // 原先的文件在释放进程的锁之后才关闭
+    let replaced = {
+        let mut inner = process.inner();
+        match inner.descriptors.get(fd).cloned().flatten() {
+            Some(file) => inner.set_descriptor(new_fd, file),
+            None => return SyscallResult::Proceed(-1),
+        }
+    };
+    match replaced {
+        Ok(_) => SyscallResult::Proceed(new_fd as isize),
+        Err(_) => SyscallResult::Proceed(-1),
+    }
+}
+
+/// 创建一个管道，将读端和写端的描述符依次写入 `fds` 指向的两个 `usize`
+///
+/// 成功时返回 0，`fds` 不是可写的用户地址或者描述符已满时返回 -1
+pub(super) fn sys_pipe(fds: *mut usize) -> SyscallResult {
+    if !user_array_valid(fds as *const usize, 2, Flags::WRITABLE) {
+        return SyscallResult::Proceed(-1);
+    }
+    let (reader, writer) = Pipe::new();
+    let reader = FileHandle::new(reader, OpenFlags::READ);
+    let writer = FileHandle::new(writer, OpenFlags::WRITE);
+    let process = PROCESSOR.lock().current_thread().process.clone();
+    let (read_fd, write_fd) = {
+        let mut inner = process.inner();
+        match (
+            inner.add_descriptor(reader.clone()),
+            inner.add_descriptor(writer.clone()),
+        ) {
+            (Ok(read_fd), Ok(write_fd)) => (read_fd, write_fd),
+            (read_fd, _) => {
+                // 只放入了读端时将其撤销，管道在释放进程的锁之后才关闭
+                if let Ok(read_fd) = read_fd {
+                    inner.remove_descriptor(read_fd);
+                }
+                return SyscallResult::Proceed(-1);
+            }
+        }
+    };
+    // 释放进程的锁之后才写入用户内存
+    let fds = unsafe { from_raw_parts_mut(fds, 2) };
+    fds[0] = read_fd;
+    fds[1] = write_fd;
+    SyscallResult::Proceed(0)
+}
 
 /// 从指定的文件中读取字符
 ///
-/// 如果缓冲区暂无数据，返回 0；出现错误返回 -1
+/// 如果暂无数据，线程会在文件中休眠等待；出现错误返回 -1
 pub(super) fn sys_read(fd: usize, buffer: *mut u8, size: usize) -> SyscallResult {
-    // 从进程中获取 inode
-    let process = PROCESSOR.lock().current_thread().process.clone();
-    if let Some(inode) = process.inner().descriptors.get(fd) {
+    if !user_buffer_valid(buffer as usize, size, Flags::WRITABLE) {
+        return SyscallResult::Proceed(-1);
+    }
+    if let Some(file) = current_file(fd) {
         // 从系统调用传入的参数生成缓冲区
         let buffer = unsafe { from_raw_parts_mut(buffer, size) };
         // 尝试读取
-        if let Ok(ret) = inode.read_at(0, buffer) {
-            let ret = ret as isize;
-            if ret > 0 {
-                return SyscallResult::Proceed(ret);
-            }
-            if ret == 0 {
-                return SyscallResult::Park(ret);
-            }
+        if let Ok(ret) = file.read(buffer) {
+            return SyscallResult::Proceed(ret as isize);
         }
     }
     SyscallResult::Proceed(-1)
 }
 
 /// 将字符写入指定的文件
-pub(super) fn sys_write(fd: usize, buffer: *mut u8, size: usize) -> SyscallResult {
-    // 从进程中获取 inode
-    let process = PROCESSOR.lock().current_thread().process.clone();
-    if let Some(inode) = process.inner().descriptors.get(fd) {
+pub(super) fn sys_write(fd: usize, buffer: *const u8, size: usize) -> SyscallResult {
+    if !user_buffer_valid(buffer as usize, size, Flags::READABLE) {
+        return SyscallResult::Proceed(-1);
+    }
+    if let Some(file) = current_file(fd) {
         // 从系统调用传入的参数生成缓冲区
-        let buffer = unsafe { from_raw_parts_mut(buffer, size) };
+        let buffer = unsafe { from_raw_parts(buffer, size) };
         // 尝试写入
-        if let Ok(ret) = inode.write_at(0, buffer) {
-            let ret = ret as isize;
-            if ret >= 0 {
-                return SyscallResult::Proceed(ret);
-            }
+        if let Ok(ret) = file.write(buffer) {
+            return SyscallResult::Proceed(ret as isize);
         }
     }
     SyscallResult::Proceed(-1)
 }
+
+/// 移动文件的读写位置
+///
+/// `whence` 为 [`SEEK_SET`]、[`SEEK_CUR`] 或 [`SEEK_END`]。返回新的位置，
+/// 文件不支持移动（例如控制台）或位置不合法时返回 -1
+pub(super) fn sys_lseek(fd: usize, offset: isize, whence: usize) -> SyscallResult {
+    let position = match whence {
+        SEEK_SET if offset >= 0 => SeekFrom::Start(offset as usize),
+        SEEK_CUR => SeekFrom::Current(offset),
+        SEEK_END => SeekFrom::End(offset),
+        _ => return SyscallResult::Proceed(-1),
+    };
+    match current_file(fd).map(|file| file.seek(position)) {
+        Some(Ok(offset)) => SyscallResult::Proceed(offset as isize),
+        _ => SyscallResult::Proceed(-1),
+    }
+}
+
+/// 在 `path` 处创建目录，成功时返回 0，上级目录不存在或文件已经存在时返回 -1
+pub(super) fn sys_mkdir(path: *const u8, length: usize) -> SyscallResult {
+    let result = user_string(path, length).ok_or(()).and_then(|path| {
+        let (directory, name) = lookup_parent(&current_cwd(), &path).map_err(|_| ())?;
+        directory.create(name, FileType::Dir, 0o777).map_err(|_| ())
+    });
+    match result {
+        Ok(_) => SyscallResult::Proceed(0),
+        Err(_) => SyscallResult::Proceed(-1),
+    }
+}
+
+/// 删除 `path` 处的文件或空目录，成功时返回 0，否则返回 -1
+///
+/// 已经打开的文件仍然可以继续读写，直到被关闭。挂载点不能删除
+pub(super) fn sys_unlink(path: *const u8, length: usize) -> SyscallResult {
+    let result = user_string(path, length).ok_or(()).and_then(|path| {
+        let (directory, name) = lookup_parent(&current_cwd(), &path).map_err(|_| ())?;
+        if is_mounted(&fs::lookup(&directory, name).map_err(|_| ())?) {
+            return Err(());
+        }
+        directory.unlink(name).map_err(|_| ())
+    });
+    match result {
+        Ok(_) => SyscallResult::Proceed(0),
+        Err(_) => SyscallResult::Proceed(-1),
+    }
+}
+
+/// 将 `old_path` 处的文件移动到 `new_path`，成功时返回 0，否则返回 -1
+///
+/// 挂载点不能移动，也不能跨越文件系统移动
+pub(super) fn sys_rename(
+    old_path: *const u8,
+    old_length: usize,
+    new_path: *const u8,
+    new_length: usize,
+) -> SyscallResult {
+    let (old_path, new_path) = match (
+        user_string(old_path, old_length),
+        user_string(new_path, new_length),
+    ) {
+        (Some(old_path), Some(new_path)) => (old_path, new_path),
+        _ => return SyscallResult::Proceed(-1),
+    };
+    let cwd = current_cwd();
+    let result = lookup_parent(&cwd, &old_path).and_then(|(old_directory, old_name)| {
+        if is_mounted(&fs::lookup(&old_directory, old_name)?) {
+            return Err(FsError::Busy);
+        }
+        let (new_directory, new_name) = lookup_parent(&cwd, &new_path)?;
+        old_directory.move_(old_name, &new_directory, new_name)
+    });
+    match result {
+        Ok(_) => SyscallResult::Proceed(0),
+        Err(_) => SyscallResult::Proceed(-1),
+    }
+}
+
+/// 将描述符 `fd` 所指文件的信息写入 `stat`
+///
+/// 成功时返回 0；描述符不存在、`stat` 不是可写的用户地址，或者文件没有这些信息（例如控制台和管道）时返回 -1
+pub(super) fn sys_fstat(fd: usize, stat: *mut Stat) -> SyscallResult {
+    if !user_array_valid(stat as *const Stat, 1, Flags::WRITABLE) {
+        return SyscallResult::Proceed(-1);
+    }
+    match current_file(fd).map(|file| file.inode.metadata()) {
+        Some(Ok(metadata)) => {
+            unsafe { *stat = Stat::from(metadata) };
+            SyscallResult::Proceed(0)
+        }
+        _ => SyscallResult::Proceed(-1),
+    }
+}
+
+/// 从目录 `fd` 的当前位置读取至多 `count` 个目录项，写入 `entries`
+///
+/// 返回读取的目录项个数，已经读完时返回 0；`fd` 不是目录或者 `entries` 放不下 `count` 项时返回 -1
+pub(super) fn sys_getdents(fd: usize, entries: *mut DirEntry, count: usize) -> SyscallResult {
+    if !user_array_valid(entries as *const DirEntry, count, Flags::WRITABLE) {
+        return SyscallResult::Proceed(-1);
+    }
+    let file = match current_file(fd) {
+        Some(file) => file,
+        None => return SyscallResult::Proceed(-1),
+    };
+    let entries = unsafe { from_raw_parts_mut(entries, count) };
+    for (i, entry) in entries.iter_mut().enumerate() {
+        match file.read_entry() {
+            Ok(Some(next)) => *entry = next,
+            Ok(None) => return SyscallResult::Proceed(i as isize),
+            Err(_) if i > 0 => return SyscallResult::Proceed(i as isize),
+            Err(_) => return SyscallResult::Proceed(-1),
+        }
+    }
+    SyscallResult::Proceed(count as isize)
+}
+
+/// 将 `source` 对应的文件系统挂载到目录 `target` 上，成功时返回 0，否则返回 -1
+///
+/// `source` 目前只支持 `diskN`，即第 N 个块设备上的 SFS（从 0 开始，前两个分别为根文件系统和交换区）
+pub(super) fn sys_mount(
+    source: *const u8,
+    source_length: usize,
+    target: *const u8,
+    target_length: usize,
+) -> SyscallResult {
+    let (source, target) = match (
+        user_string(source, source_length),
+        user_string(target, target_length),
+    ) {
+        (Some(source), Some(target)) => (source, target),
+        _ => return SyscallResult::Proceed(-1),
+    };
+    match fs::lookup(&current_cwd(), &target).and_then(|point| fs::mount(&source, point)) {
+        Ok(()) => SyscallResult::Proceed(0),
+        Err(_) => SyscallResult::Proceed(-1),
+    }
+}
+
+/// 卸载挂载在 `target` 上的文件系统，成功时返回 0，否则返回 -1
+///
+/// 其中还挂载有其他文件系统，或者还有打开的文件时不能卸载
+pub(super) fn sys_umount(target: *const u8, length: usize) -> SyscallResult {
+    let target = match user_string(target, length) {
+        Some(target) => target,
+        None => return SyscallResult::Proceed(-1),
+    };
+    match fs::lookup(&current_cwd(), &target).and_then(|root| fs::umount(&root)) {
+        Ok(()) => SyscallResult::Proceed(0),
+        Err(_) => SyscallResult::Proceed(-1),
+    }
+}
+
+/// 将文件系统的所有修改写回磁盘，成功时返回 0，失败时返回 -1
+pub(super) fn sys_sync() -> SyscallResult {
+    match fs::sync() {
+        Ok(()) => SyscallResult::Proceed(0),
+        Err(_) => SyscallResult::Proceed(-1),
+    }
+}
+
+/// 将描述符 `fd` 所指文件的修改写回磁盘
+///
+/// 成功时返回 0；描述符不存在或者文件不支持（例如控制台和管道）时返回 -1
+pub(super) fn sys_fsync(fd: usize) -> SyscallResult {
+    match current_file(fd).map(|file| file.sync()) {
+        Some(Ok(())) => SyscallResult::Proceed(0),
+        _ => SyscallResult::Proceed(-1),
+    }
+}
+
+/// 将当前工作目录切换到 `path`，成功时返回 0，`path` 不存在或不是目录时返回 -1
+pub(super) fn sys_chdir(path: *const u8, length: usize) -> SyscallResult {
+    let path = match user_string(path, length) {
+        Some(path) => path,
+        None => return SyscallResult::Proceed(-1),
+    };
+    let process = PROCESSOR.lock().current_thread().process.clone();
+    let (cwd, cwd_path) = {
+        let inner = process.inner();
+        (inner.cwd.clone(), inner.cwd_path.clone())
+    };
+    match fs::lookup(&cwd, &path) {
+        Ok(directory) if matches!(directory.metadata(), Ok(m) if m.type_ == FileType::Dir) => {
+            let mut inner = process.inner();
+            inner.cwd = directory;
+            inner.cwd_path = join_path(&cwd_path, &path);
+            SyscallResult::Proceed(0)
+        }
+        _ => SyscallResult::Proceed(-1),
+    }
+}
+
+/// 将当前工作目录的绝对路径写入 `buffer`（不以 `\0` 结尾）
+///
+/// 返回路径的长度，`buffer` 放不下或者不是可写的用户地址时返回 -1
+pub(super) fn sys_getcwd(buffer: *mut u8, size: usize) -> SyscallResult {
+    let process = PROCESSOR.lock().current_thread().process.clone();
+    // 复制出来之后再写入用户内存
+    let cwd_path = process.inner().cwd_path.clone();
+    if size < cwd_path.len() || !user_buffer_valid(buffer as usize, cwd_path.len(), Flags::WRITABLE)
+    {
+        return SyscallResult::Proceed(-1);
+    }
+    let buffer = unsafe { from_raw_parts_mut(buffer, cwd_path.len()) };
+    buffer.copy_from_slice(cwd_path.as_bytes());
+    SyscallResult::Proceed(cwd_path.len() as isize)
+}
+
+/// 当前进程的工作目录
+pub(super) fn current_cwd() -> Arc<dyn INode> {
+    let process = PROCESSOR.lock().current_thread().process.clone();
+    let cwd = process.inner().cwd.clone();
+    cwd
+}
+
+/// 取出当前进程中描述符 `fd` 对应的文件
+///
+/// 取出后即释放进程的锁，因为之后访问用户内存时可能发生缺页，需要由进程处理
+pub(super) fn current_file(fd: usize) -> Option<Arc<FileHandle>> {
+    let process = PROCESSOR.lock().current_thread().process.clone();
+    let file = process.inner().descriptors.get(fd).cloned().flatten();
+    file
+}
@@ -0,0 +1,147 @@
+//! 内存映射相关的内核功能
+
+use super::*;
+use crate::fs::{FileType, OpenFlags};
+use crate::memory::{Flags, MapType, VirtualAddress, PAGE_SIZE};
+use core::cmp::min;
+
+/// 映射可读
+pub const PROT_READ: usize = 1 << 0;
+/// 映射可写
+pub const PROT_WRITE: usize = 1 << 1;
+/// 映射可执行
+pub const PROT_EXEC: usize = 1 << 2;
+
+/// 共享映射：fork 之后父子进程共享，文件映射的修改会写回文件
+pub const MAP_SHARED: usize = 1 << 0;
+/// 私有映射：fork 之后写时复制，文件映射的修改不会写回文件
+pub const MAP_PRIVATE: usize = 1 << 1;
+/// 匿名映射，内容初始为零，不使用 `fd` 和 `offset`
+pub const MAP_ANONYMOUS: usize = 1 << 5;
+
+/// 将 `PROT_` 开头的权限转换为 [`Flags`]，含有未知的位时返回 `None`
+fn prot_flags(prot: usize) -> Option<Flags> {
+    if prot & !(PROT_READ | PROT_WRITE | PROT_EXEC) != 0 {
+        return None;
+    }
+    Some(
+        Flags::readable(prot & PROT_READ != 0)
+            | Flags::writable(prot & PROT_WRITE != 0)
+            | Flags::executable(prot & PROT_EXEC != 0),
+    )
+}
+
+/// 建立一段长度为 `size` 的映射，返回其起始地址
+///
+/// 由内核选择映射的地址，`address` 必须为 0。`flags` 中 [`MAP_SHARED`] 和 [`MAP_PRIVATE`] 必须选择其一；
+/// 不是 [`MAP_ANONYMOUS`] 时映射文件 `fd` 中从 `offset` 开始的内容，`offset` 必须按页对齐，
+/// 超出文件末尾的部分填充为零。只能映射普通文件，设备不能映射。
+/// 私有的匿名映射在第一次访问时才分配物理页，其他映射立即分配，文件的内容逐页读入物理页。
+/// 参数不合法或者文件无法按要求映射时返回 -1
+pub(super) fn sys_mmap(
+    address: usize,
+    size: usize,
+    prot: usize,
+    flags: usize,
+    fd: usize,
+    offset: usize,
+) -> SyscallResult {
+    let page_flags = match prot_flags(prot) {
+        Some(page_flags) => page_flags,
+        None => return SyscallResult::Proceed(-1),
+    };
+    let shared = match flags & (MAP_SHARED | MAP_PRIVATE) {
+        MAP_SHARED => true,
+        MAP_PRIVATE => false,
+        _ => return SyscallResult::Proceed(-1),
+    };
+    // 长度不能超过物理内存和交换区能够容纳的总量
+    if address != 0
+        || size == 0
+        || size > MAX_MAP_SIZE
+        || flags & !(MAP_SHARED | MAP_PRIVATE | MAP_ANONYMOUS) != 0
+    {
+        return SyscallResult::Proceed(-1);
+    }
+    let process = PROCESSOR.lock().current_thread().process.clone();
+
+    // 匿名映射
+    if flags & MAP_ANONYMOUS != 0 {
+        let map_type = if shared {
+            MapType::Shared
+        } else {
+            MapType::Lazy
+        };
+        return match process.map_range(size, map_type, page_flags, None) {
+            Ok(range) => SyscallResult::Proceed(range.start.0 as isize),
+            Err(_) => SyscallResult::Proceed(-1),
+        };
+    }
+
+    // 文件映射，需要以可读的方式打开；共享且可写的映射会写回文件，还需要可写
+    let file = match current_file(fd) {
+        Some(file) => file,
+        None => return SyscallResult::Proceed(-1),
+    };
+    let writes_back = shared && page_flags.contains(Flags::WRITABLE);
+    if offset % PAGE_SIZE != 0
+        || !file.flags.contains(OpenFlags::READ)
+        || (writes_back && !file.flags.contains(OpenFlags::WRITE))
+    {
+        return SyscallResult::Proceed(-1);
+    }
+    // 设备报告的长度为 0，写回时会被截断，因此只映射普通文件
+    let file_size = match file.inode.metadata() {
+        Ok(metadata) if metadata.type_ == FileType::File => metadata.size,
+        _ => return SyscallResult::Proceed(-1),
+    };
+    // 私有的文件映射同样立即分配，fork 之后写时复制
+    let map_type = if shared {
+        MapType::Shared
+    } else {
+        MapType::Framed
+    };
+    // 只读取文件中存在的部分
+    let length = min(size, file_size.saturating_sub(offset));
+    let range = match process.map_file(size, map_type, page_flags, &file.inode, offset, length) {
+        Ok(range) => range,
+        Err(_) => return SyscallResult::Proceed(-1),
+    };
+    if writes_back {
+        process.inner().file_mappings.insert(
+            range.start,
+            FileMapping {
+                inode: file.inode.clone(),
+                offset,
+                size,
+            },
+        );
+    }
+    SyscallResult::Proceed(range.start.0 as isize)
+}
+
+/// 解除 [`sys_mmap`] 建立的映射，共享的文件映射会写回文件
+///
+/// `[address, address + size)` 必须恰好是一段映射（长度按页取整），成功时返回 0，否则返回 -1
+pub(super) fn sys_munmap(address: usize, size: usize) -> SyscallResult {
+    let process = PROCESSOR.lock().current_thread().process.clone();
+    match process.unmap_range(VirtualAddress(address), size) {
+        Ok(()) => SyscallResult::Proceed(0),
+        Err(_) => SyscallResult::Proceed(-1),
+    }
+}
+
+/// 将一段映射的权限改为 `prot`，已经建立的页表项一并修改
+///
+/// `[address, address + size)` 必须恰好是一段映射（长度按页取整），成功时返回 0，否则返回 -1
+pub(super) fn sys_mprotect(address: usize, size: usize, prot: usize) -> SyscallResult {
+    let flags = match prot_flags(prot) {
+        Some(flags) => flags,
+        None => return SyscallResult::Proceed(-1),
+    };
+    let process = PROCESSOR.lock().current_thread().process.clone();
+    match process.protect_range(VirtualAddress(address), size, flags) {
+        Ok(()) => SyscallResult::Proceed(0),
+        Err(_) => SyscallResult::Proceed(-1),
+    }
+}
@@ -2,6 +2,7 @@
 
 mod condvar;
 mod fs;
+mod mmap;
 mod process;
 mod syscall;
 
@@ -9,6 +10,7 @@ use crate::interrupt::*;
 use crate::process::*;
 use alloc::sync::Arc;
 pub(self) use fs::*;
+pub(self) use mmap::*;
 pub(self) use process::*;
 use spin::Mutex;
 pub(self) use syscall::*;
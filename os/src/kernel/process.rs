@@ -1,12 +1,142 @@
 //! 进程相关的内核功能
 
 use super::*;
+use crate::fs::{lookup, INodeExt};
+use crate::memory::Flags;
+use xmas_elf::ElfFile;
 
+/// 结束当前进程，`code` 作为退出码保留到父进程回收为止
 pub(super) fn sys_exit(code: usize) -> SyscallResult {
-    println!(
-        "thread {} exit with code {}",
-        PROCESSOR.lock().current_thread().id,
-        code
-    );
+    let process = PROCESSOR.lock().current_thread().process.clone();
+    println!("process {} exit with code {}", process.pid, code as isize);
+    process.exit(code as isize);
     SyscallResult::Kill
 }
+
+/// 获取当前进程的 ID
+pub(super) fn sys_getpid() -> SyscallResult {
+    SyscallResult::Proceed(PROCESSOR.lock().current_thread().process.pid)
+}
+
+/// 设置当前线程的调度优先级，越大则分到的时间片越多
+///
+/// 只有启用 `stride` 特性、使用步幅调度时优先级才起作用，默认的多级反馈队列调度会忽略它。
+/// 成功时返回设置的优先级，优先级不是正数时返回 -1
+pub(super) fn sys_set_priority(priority: isize) -> SyscallResult {
+    if priority <= 0 {
+        return SyscallResult::Proceed(-1);
+    }
+    PROCESSOR.lock().set_current_priority(priority as usize);
+    SyscallResult::Proceed(priority)
+}
+
+/// 等待 ID 为 `pid` 的子进程退出（`pid` 为 -1 时等待任一子进程），并回收它
+///
+/// 返回子进程的 ID，并将退出码写入 `exit_code`（可以为空指针）；没有符合的子进程或者地址不合法时返回 -1。
+/// 如果子进程尚未退出，则当前线程休眠，直到有子进程退出
+pub(super) fn sys_waitpid(pid: ProcessID, exit_code: *mut isize) -> SyscallResult {
+    // 在回收子进程之前检查，地址不合法时子进程保持不变
+    if !exit_code.is_null() && !user_array_valid(exit_code as *const isize, 1, Flags::WRITABLE) {
+        return SyscallResult::Proceed(-1);
+    }
+    let process = PROCESSOR.lock().current_thread().process.clone();
+    loop {
+        let mut inner = process.inner();
+        if !inner.children.iter().any(|child| pid == -1 || child.pid == pid) {
+            return SyscallResult::Proceed(-1);
+        }
+        // 寻找已经退出的子进程
+        let zombie = inner.children.iter().position(|child| {
+            (pid == -1 || child.pid == pid) && child.inner().exit_code.is_some()
+        });
+        match zombie {
+            Some(index) => {
+                let child = inner.children.remove(index);
+                // 写入用户内存时可能发生缺页，需要先释放进程的锁
+                drop(inner);
+                let code = child.inner().exit_code.unwrap();
+                if !exit_code.is_null() {
+                    unsafe { *exit_code = code };
+                }
+                return SyscallResult::Proceed(child.pid);
+            }
+            None => {
+                // 进程的锁在登记为等待者之后才释放，以免错过子进程的退出
+                process.child_exited.wait(inner);
+            }
+        }
+    }
+}
+
+/// 复制当前的进程，子进程中只有当前线程的一份拷贝
+///
+/// 父进程中返回子进程的 ID，子进程中返回 0；失败时返回 -1
+pub(super) fn sys_fork(context: &Context) -> SyscallResult {
+    let thread = PROCESSOR.lock().current_thread();
+    let process = match thread.process.fork() {
+        Ok(process) => process,
+        Err(_) => return SyscallResult::Proceed(-1),
+    };
+    // 子线程从同样的位置继续执行，但返回值为 0
+    let mut child_context = *context;
+    child_context.x[10] = 0;
+    let pid = process.pid;
+    match thread.fork(process, child_context) {
+        Ok(child) => {
+            PROCESSOR.lock().add_thread(child);
+            SyscallResult::Proceed(pid)
+        }
+        Err(_) => {
+            // 无法创建线程，撤销子进程
+            thread.process.inner().children.retain(|child| child.pid != pid);
+            SyscallResult::Proceed(-1)
+        }
+    }
+}
+
+/// 用文件系统中的另一个程序替换当前进程
+///
+/// 成功时当前线程从新程序的入口开始执行，进程中的其他线程都会被结束；找不到或无法解析程序时返回 -1
+pub(super) fn sys_exec(path: *const u8, length: usize, context: &mut Context) -> SyscallResult {
+    // 先将路径复制出来，因为旧的内存映射将被释放
+    let path = match user_string(path, length) {
+        Some(path) => path,
+        None => return SyscallResult::Proceed(-1),
+    };
+    // 从文件系统中找到程序并读取，相对路径从工作目录开始查找
+    let data = match lookup(&current_cwd(), &path).and_then(|app| app.readall()) {
+        Ok(data) => data,
+        Err(_) => return SyscallResult::Proceed(-1),
+    };
+    // 解析 ELF 文件
+    let elf = match ElfFile::new(data.as_slice()) {
+        Ok(elf) => elf,
+        Err(_) => return SyscallResult::Proceed(-1),
+    };
+
+    // 替换内存映射
+    let thread = PROCESSOR.lock().current_thread();
+    if thread.process.exec(&elf, &thread).is_err() {
+        return SyscallResult::Proceed(-1);
+    }
+    // 此时旧的映射已经释放，如果无法分配新的栈，则只能终止进程，让父进程能够回收
+    let stack = match thread
+        .process
+        .alloc_page_range(STACK_SIZE, Flags::READABLE | Flags::WRITABLE)
+    {
+        Ok(stack) => stack,
+        Err(_) => {
+            thread.process.exit(-1);
+            return SyscallResult::Kill;
+        }
+    };
+    thread.inner().stack = stack;
+    // 从新程序的入口开始执行
+    *context = Context::new(
+        stack.end.into(),
+        elf.header.pt2.entry_point() as usize,
+        None,
+        thread.process.is_user,
+    );
+    SyscallResult::Proceed(0)
+}
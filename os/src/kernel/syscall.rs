@@ -1,35 +1,103 @@
 //! 实现各种系统调用
 
 use super::*;
+use crate::fs::{DirEntry, Stat};
+use crate::memory::{Flags, Range, VirtualAddress};
+use alloc::string::String;
+use core::mem::size_of;
+use core::slice::from_raw_parts;
 
+pub const SYS_GETCWD: usize = 17;
+pub const SYS_DUP: usize = 23;
+/// 对应 Linux 的 dup3，但不带 `flags` 参数
+pub const SYS_DUP2: usize = 24;
+pub const SYS_MKDIR: usize = 34;
+pub const SYS_UNLINK: usize = 35;
+pub const SYS_RENAME: usize = 38;
+/// 对应 Linux 的 umount2，但不带 `flags` 参数
+pub const SYS_UMOUNT: usize = 39;
+/// 参数与 Linux 不同，只有来源和挂载点
+pub const SYS_MOUNT: usize = 40;
+pub const SYS_CHDIR: usize = 49;
+pub const SYS_OPEN: usize = 56;
+pub const SYS_CLOSE: usize = 57;
+pub const SYS_PIPE: usize = 59;
+pub const SYS_GETDENTS: usize = 61;
+pub const SYS_LSEEK: usize = 62;
 pub const SYS_READ: usize = 63;
 pub const SYS_WRITE: usize = 64;
+pub const SYS_FSTAT: usize = 80;
+pub const SYS_SYNC: usize = 81;
+pub const SYS_FSYNC: usize = 82;
 pub const SYS_EXIT: usize = 93;
+pub const SYS_SET_PRIORITY: usize = 140;
+pub const SYS_GETPID: usize = 172;
+pub const SYS_MUNMAP: usize = 215;
+pub const SYS_FORK: usize = 220;
+pub const SYS_EXEC: usize = 221;
+pub const SYS_MMAP: usize = 222;
+pub const SYS_MPROTECT: usize = 226;
+pub const SYS_WAITPID: usize = 260;
 
 /// 系统调用在内核之内的返回值
 pub(super) enum SyscallResult {
     /// 继续执行，带返回值
     Proceed(isize),
-    /// 记录返回值，但暂存当前线程
-    Park(isize),
     /// 丢弃当前 context，调度下一个线程继续执行
     Kill,
 }
 
 /// 系统调用的总入口
-pub fn syscall_handler(context: &mut Context) -> *mut Context {
+pub fn syscall_handler(context: &mut Context) {
     // 无论如何处理，一定会跳过当前的 ecall 指令
     context.sepc += 4;
 
     let syscall_id = context.x[17];
-    let args = [context.x[10], context.x[11], context.x[12]];
+    let args = [
+        context.x[10],
+        context.x[11],
+        context.x[12],
+        context.x[13],
+        context.x[14],
+        context.x[15],
+    ];
 
     let result = match syscall_id {
+        SYS_GETCWD => sys_getcwd(args[0] as *mut u8, args[1]),
+        SYS_DUP => sys_dup(args[0]),
+        SYS_DUP2 => sys_dup2(args[0], args[1]),
+        SYS_CHDIR => sys_chdir(args[0] as *const u8, args[1]),
+        SYS_OPEN => sys_open(args[0] as *const u8, args[1], args[2]),
+        SYS_CLOSE => sys_close(args[0]),
+        SYS_PIPE => sys_pipe(args[0] as *mut usize),
+        SYS_MKDIR => sys_mkdir(args[0] as *const u8, args[1]),
+        SYS_UNLINK => sys_unlink(args[0] as *const u8, args[1]),
+        SYS_RENAME => sys_rename(args[0] as *const u8, args[1], args[2] as *const u8, args[3]),
+        SYS_UMOUNT => sys_umount(args[0] as *const u8, args[1]),
+        SYS_MOUNT => sys_mount(args[0] as *const u8, args[1], args[2] as *const u8, args[3]),
+        SYS_FSTAT => sys_fstat(args[0], args[1] as *mut Stat),
+        SYS_GETDENTS => sys_getdents(args[0], args[1] as *mut DirEntry, args[2]),
+        SYS_SYNC => sys_sync(),
+        SYS_FSYNC => sys_fsync(args[0]),
         SYS_READ => sys_read(args[0], args[1] as *mut u8, args[2]),
-        SYS_WRITE => sys_write(args[0], args[1] as *mut u8, args[2]),
+        SYS_WRITE => sys_write(args[0], args[1] as *const u8, args[2]),
+        SYS_LSEEK => sys_lseek(args[0], args[1] as isize, args[2]),
         SYS_EXIT => sys_exit(args[0]),
+        SYS_FORK => sys_fork(context),
+        SYS_EXEC => sys_exec(args[0] as *const u8, args[1], context),
+        SYS_WAITPID => sys_waitpid(args[0] as ProcessID, args[1] as *mut isize),
+        SYS_GETPID => sys_getpid(),
+        SYS_MMAP => sys_mmap(args[0], args[1], args[2], args[3], args[4], args[5]),
+        SYS_MUNMAP => sys_munmap(args[0], args[1]),
+        SYS_MPROTECT => sys_mprotect(args[0], args[1], args[2]),
+        SYS_SET_PRIORITY => sys_set_priority(args[0] as isize),
         _ => {
             println!("unimplemented syscall: {}", syscall_id);
+            // 与异常终止相同，结束用户进程，让父进程能够回收
+            let process = PROCESSOR.lock().current_thread().process.clone();
+            if process.is_user {
+                process.exit(-1);
+            }
             SyscallResult::Kill
         }
     };
@@ -38,19 +106,43 @@ pub fn syscall_handler(context: &mut Context) -> *mut Context {
         SyscallResult::Proceed(ret) => {
             // 将返回值放入 context 中
             context.x[10] = ret as usize;
-            context
-        }
-        SyscallResult::Park(ret) => {
-            // 将返回值放入 context 中
-            context.x[10] = ret as usize;
-            // 保存 context，准备下一个线程
-            PROCESSOR.lock().park_current_thread(context);
-            PROCESSOR.lock().prepare_next_thread()
         }
         SyscallResult::Kill => {
             // 终止，跳转到 PROCESSOR 调度的下一个线程
-            PROCESSOR.lock().kill_current_thread();
-            PROCESSOR.lock().prepare_next_thread()
+            exit_current_thread();
         }
     }
 }
+
+/// 当前进程中 `[pointer, pointer + length)` 是否都已映射且具有 `access` 权限
+///
+/// 访问用户内存的系统调用先进行检查，非法的地址直接返回错误，而不会在内核中发生无法处理的缺页
+pub(super) fn user_buffer_valid(pointer: usize, length: usize, access: Flags) -> bool {
+    let end = match pointer.checked_add(length) {
+        Some(end) if pointer != 0 => end,
+        _ => return false,
+    };
+    let process = PROCESSOR.lock().current_thread().process.clone();
+    let valid = process.inner().memory_set.contains_range(
+        Range::from(VirtualAddress(pointer)..VirtualAddress(end)),
+        access,
+    );
+    valid
+}
+
+/// 当前进程中从 `pointer` 开始的 `count` 个 `T` 是否都已映射且具有 `access` 权限，总长度溢出时视为非法
+pub(super) fn user_array_valid<T>(pointer: *const T, count: usize, access: Flags) -> bool {
+    match count.checked_mul(size_of::<T>()) {
+        Some(length) => user_buffer_valid(pointer as usize, length, access),
+        None => false,
+    }
+}
+
+/// 从用户进程的内存中复制出一个字符串，地址不合法或不是合法的 UTF-8 时返回 `None`
+pub(super) fn user_string(pointer: *const u8, length: usize) -> Option<String> {
+    if !user_buffer_valid(pointer as usize, length, Flags::READABLE) {
+        return None;
+    }
+    let bytes = unsafe { from_raw_parts(pointer, length) };
+    core::str::from_utf8(bytes).ok().map(String::from)
+}
@@ -43,6 +43,7 @@
 mod console;
 mod drivers;
 mod fs;
+mod hart;
 mod interrupt;
 mod kernel;
 mod memory;
@@ -62,9 +63,15 @@ global_asm!(include_str!("entry.asm"));
 
 /// Rust 的入口函数
 ///
-/// 在 `_start` 为我们进行了一系列准备之后，这是第一个被调用的 Rust 函数
+/// 在 `_start` 为我们进行了一系列准备之后，这是第一个被调用的 Rust 函数。
+/// 每个 hart 都会进入这里，第一个到达的负责初始化，然后启动其他 hart
 #[no_mangle]
-pub extern "C" fn rust_main(_hart_id: usize, dtb_pa: PhysicalAddress) -> ! {
+pub extern "C" fn rust_main(hart_id: usize, dtb_pa: PhysicalAddress) -> ! {
+    if !hart::claim_boot() {
+        hart::wait_for_boot();
+        secondary_main(hart_id)
+    }
+
     memory::init();
     interrupt::init();
     drivers::init(dtb_pa);
@@ -82,16 +89,20 @@ pub extern "C" fn rust_main(_hart_id: usize, dtb_pa: PhysicalAddress) -> ! {
                 Some(&[i]),
             ));
         }
+        // 定期写回文件系统的线程
+        processor.add_thread(create_kernel_thread(kernel_process, fs::sync_thread as usize, None));
     }
 
-    extern "C" {
-        fn __restore(context: usize);
-    }
-    // 获取第一个线程的 Context
-    let context = PROCESSOR.lock().prepare_next_thread();
-    // 启动第一个线程
-    unsafe { __restore(context as usize) };
-    unreachable!()
+    // 启动其他 hart，然后在启动栈上运行调度循环，开始执行线程
+    hart::finish_boot(dtb_pa);
+    run_scheduler()
+}
+
+/// 其他 hart 的入口，初始化自己的中断之后参与调度
+fn secondary_main(hart_id: usize) -> ! {
+    interrupt::init_secondary();
+    println!("hart {} started", hart_id);
+    run_scheduler()
 }
 
 fn sample_process(id: usize) {
@@ -106,14 +117,8 @@ pub fn create_kernel_thread(
 ) -> Arc<Thread> {
     // 创建线程
     let thread = Thread::new(process, entry_point, arguments).unwrap();
-    // 设置线程的返回地址为 kernel_thread_exit
-    thread
-        .as_ref()
-        .inner()
-        .context
-        .as_mut()
-        .unwrap()
-        .set_ra(kernel_thread_exit as usize);
+    // 设置线程的返回地址为 kernel_thread_exit（线程尚未执行，其 Context 位于内核栈顶）
+    unsafe { (*thread.kernel_stack.context()).set_ra(kernel_thread_exit as usize) };
 
     thread
 }
@@ -25,9 +25,20 @@ lazy_static! {
 /// 操作系统动态分配内存所用的堆大小（8M）
 pub const KERNEL_HEAP_SIZE: usize = 0x80_0000;
 
+/// 共享内核区域的起始地址（根页表第 509 项），用于线程的内核栈
+pub const KERNEL_AREA_START: VirtualAddress = VirtualAddress(0xffff_ffff_4000_0000);
+/// 共享内核区域的大小（1G）
+pub const KERNEL_AREA_SIZE: usize = 0x4000_0000;
+
+/// 交换区可以容纳的页面数量（16M），交换区设备的大小不能小于此
+pub const SWAP_PAGE_COUNT: usize = 0x1000;
+
 /// 内核使用线性映射的偏移量
 pub const KERNEL_MAP_OFFSET: usize = 0xffff_ffff_0000_0000;
 
+/// 用户部分虚拟地址空间的上界（Sv39 的低半部分）
+pub const USER_END_ADDRESS: usize = 0x40_0000_0000;
+
 extern "C" {
     /// 由 `linker.ld` 指定的内核代码结束位置
     ///
@@ -1,6 +1,7 @@
 //! 提供帧分配器 [`FRAME_ALLOCATOR`](FrameAllocator)
 //!
 //! 返回的 [`FrameTracker`] 类型代表一个帧，它在被 drop 时会自动将空间补回分配器中。
+//! 连续分配返回的 [`FrameRangeTracker`] 代表一段连续的帧，同样在被 drop 时整段回收。
 
 use super::*;
 use crate::memory::*;
@@ -10,26 +11,33 @@ use spin::Mutex;
 
 lazy_static! {
     /// 帧分配器
-    pub static ref FRAME_ALLOCATOR: Mutex<FrameAllocator<AllocatorImpl>> = Mutex::new(FrameAllocator::new(Range::from(
+    pub static ref FRAME_ALLOCATOR: Mutex<FrameAllocator<ContiguousAllocatorImpl>> = Mutex::new(FrameAllocator::new(Range::from(
             PhysicalPageNumber::ceil(PhysicalAddress::from(*KERNEL_END_ADDRESS))..PhysicalPageNumber::floor(MEMORY_END_ADDRESS),
         )
     ));
 }
 
-/// 基于线段树的帧分配 / 回收
-pub struct FrameAllocator<T: Allocator> {
-    /// 可用区间的起始
-    start_ppn: PhysicalPageNumber,
+/// 帧分配 / 回收
+///
+/// 分配器中的序号是相对于 `base_ppn` 的偏移。`base_ppn` 对齐到不小于可用区间长度的 2 的幂，
+/// 因此按序号对齐的连续分配，其物理地址也同样对齐。`base_ppn` 与可用区间之间的物理页在创建时被标记为已分配
+pub struct FrameAllocator<T: ContiguousAllocator> {
+    /// 序号 0 对应的物理页号
+    base_ppn: PhysicalPageNumber,
     /// 分配器
     allocator: T,
 }
 
-impl<T: Allocator> FrameAllocator<T> {
+impl<T: ContiguousAllocator> FrameAllocator<T> {
     /// 创建对象
     pub fn new(range: impl Into<Range<PhysicalPageNumber>> + Copy) -> Self {
+        let range = range.into();
+        let base = range.start.0 & !(range.len().next_power_of_two() - 1);
+        let mut allocator = T::new(range.end.0 - base);
+        allocator.reserve(0, range.start.0 - base);
         FrameAllocator {
-            start_ppn: range.into().start,
-            allocator: T::new(range.into().len()),
+            base_ppn: PhysicalPageNumber(base),
+            allocator,
         }
     }
 
@@ -38,13 +46,33 @@ impl<T: Allocator> FrameAllocator<T> {
         self.allocator
             .alloc()
             .ok_or("no available frame to allocate")
-            .map(|offset| FrameTracker(self.start_ppn + offset))
+            .map(|offset| FrameTracker(self.base_ppn + offset))
     }
 
-    /// 将被释放的帧添加到空闲列表的尾部
+    /// 分配连续的 `count` 个帧，起始物理页号是 `align` 的倍数（`align` 为 2 的幂）
+    pub fn alloc_contiguous(
+        &mut self,
+        count: usize,
+        align: usize,
+    ) -> MemoryResult<FrameRangeTracker> {
+        self.allocator
+            .alloc_contiguous(count, align)
+            .ok_or("no available contiguous frames to allocate")
+            .map(|offset| FrameRangeTracker::new(self.base_ppn + offset, count))
+    }
+
+    /// 将被释放的帧放回分配器
     ///
     /// 这个函数会在 [`FrameTracker`] 被 drop 时自动调用，不应在其他地方调用
     pub(super) fn dealloc(&mut self, frame: &FrameTracker) {
-        self.allocator.dealloc(frame.page_number() - self.start_ppn);
+        self.allocator.dealloc(frame.page_number() - self.base_ppn);
+    }
+
+    /// 将被释放的连续帧放回分配器
+    ///
+    /// 这个函数会在 [`FrameRangeTracker`] 被 drop 时自动调用，不应在其他地方调用
+    pub(super) fn dealloc_contiguous(&mut self, frames: &FrameRangeTracker) {
+        self.allocator
+            .dealloc_contiguous(frames.page_number() - self.base_ppn, frames.len());
     }
 }
@@ -1,4 +1,4 @@
-//! 提供物理页的「`Box`」 [`FrameTracker`]
+//! 提供物理页的「`Box`」 [`FrameTracker`]，以及连续物理页的 [`FrameRangeTracker`]
 
 use crate::memory::{address::*, FRAME_ALLOCATOR, PAGE_SIZE};
 
@@ -53,3 +53,63 @@ impl Drop for FrameTracker {
         FRAME_ALLOCATOR.lock().dealloc(self);
     }
 }
+
+/// 连续分配出的一段物理页，由 [`FrameAllocator::alloc_contiguous`] 分配
+///
+/// 与 [`FrameTracker`] 相同，在被 drop 时整段放回 [`static@FRAME_ALLOCATOR`]。
+/// 用于设备 DMA 等需要物理地址连续的场合
+///
+/// [`FrameAllocator::alloc_contiguous`]: super::allocator::FrameAllocator::alloc_contiguous
+pub struct FrameRangeTracker {
+    /// 起始物理页号
+    start: PhysicalPageNumber,
+    /// 物理页数量
+    count: usize,
+}
+
+impl FrameRangeTracker {
+    /// 由分配器创建
+    pub(super) fn new(start: PhysicalPageNumber, count: usize) -> Self {
+        Self { start, count }
+    }
+    /// 起始物理地址
+    pub fn address(&self) -> PhysicalAddress {
+        self.start.into()
+    }
+    /// 起始物理页号
+    pub fn page_number(&self) -> PhysicalPageNumber {
+        self.start
+    }
+    /// 物理页数量
+    pub fn len(&self) -> usize {
+        self.count
+    }
+    /// 是否不含任何物理页
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+}
+
+/// `FrameRangeTracker` 可以 deref 得到对应的 `[u8]`，长度为所有页的总大小
+impl core::ops::Deref for FrameRangeTracker {
+    type Target = [u8];
+    fn deref(&self) -> &Self::Target {
+        let address = VirtualAddress::from(self.address());
+        unsafe { core::slice::from_raw_parts(address.0 as *const u8, self.count * PAGE_SIZE) }
+    }
+}
+
+/// `FrameRangeTracker` 可以 deref 得到对应的 `[u8]`，长度为所有页的总大小
+impl core::ops::DerefMut for FrameRangeTracker {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        let address = VirtualAddress::from(self.address());
+        unsafe { core::slice::from_raw_parts_mut(address.0 as *mut u8, self.count * PAGE_SIZE) }
+    }
+}
+
+/// 连续的帧在释放时整段放回 [`static@FRAME_ALLOCATOR`]
+impl Drop for FrameRangeTracker {
+    fn drop(&mut self) {
+        FRAME_ALLOCATOR.lock().dealloc_contiguous(self);
+    }
+}
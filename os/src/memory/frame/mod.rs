@@ -4,4 +4,4 @@ mod allocator;
 mod frame_tracker;
 
 pub use allocator::FRAME_ALLOCATOR;
-pub use frame_tracker::FrameTracker;
+pub use frame_tracker::{FrameRangeTracker, FrameTracker};
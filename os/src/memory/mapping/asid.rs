@@ -0,0 +1,113 @@
+//! 地址空间标识符（ASID）的分配 [`Asid`]
+//!
+//! `satp` 中带有 ASID，TLB 中不同 ASID 的项互不干扰，因此切换页表时不需要刷新整个 TLB。
+//! ASID 按代分配：每一代中从 1 开始依次分配，分完之后进入下一代，所有映射需要重新分配，
+//! 各个 hart 在下一次切换页表时刷新整个 TLB。0 保留给启动页表。
+
+use crate::hart::{hart_id, MAX_HART_COUNT};
+use core::sync::atomic::{AtomicUsize, Ordering};
+use lazy_static::*;
+use spin::Mutex;
+
+/// `satp` 中 ASID 字段的起始位置
+pub const ASID_SHIFT: usize = 44;
+/// `satp` 中 ASID 字段的最大宽度（Sv39 中为 16 位）
+const ASID_MAX_BITS: usize = 16;
+
+lazy_static! {
+    /// 硬件支持的 ASID 数量，为 1 时表示不支持 ASID
+    static ref ASID_COUNT: usize = probe_asid_count();
+    /// 全局的 ASID 分配器，只在需要分配时使用
+    static ref ASID_ALLOCATOR: Mutex<AsidAllocator> = Mutex::new(AsidAllocator { next: 1 });
+}
+
+/// 当前的代数，从 1 开始。只在持有 [`static@ASID_ALLOCATOR`] 时修改
+static GENERATION: AtomicUsize = AtomicUsize::new(1);
+
+/// 进入新一代之后还没有刷新过 TLB 的 hart
+static STALE_HARTS: AtomicUsize = AtomicUsize::new(0);
+
+/// 检测硬件支持的 ASID 位数
+///
+/// 向 ASID 字段写入全 1 再读回，保留下来的位即为硬件支持的部分。
+/// 探测期间可能以全 1 的 ASID 填充了 TLB，因此恢复 `satp` 之后刷新 TLB
+fn probe_asid_count() -> usize {
+    let mask = ((1 << ASID_MAX_BITS) - 1) << ASID_SHIFT;
+    let satp: usize;
+    let supported: usize;
+    unsafe {
+        llvm_asm!("csrr $0, satp" : "=r"(satp) ::: "volatile");
+        llvm_asm!("csrw satp, $0" :: "r"(satp | mask) :: "volatile");
+        llvm_asm!("csrr $0, satp" : "=r"(supported) ::: "volatile");
+        llvm_asm!("csrw satp, $0" :: "r"(satp) :: "volatile");
+        llvm_asm!("sfence.vma" :::: "volatile");
+    }
+    ((supported & mask) >> ASID_SHIFT) + 1
+}
+
+/// 按代分配 ASID
+struct AsidAllocator {
+    /// 这一代中下一个分配的 ASID
+    next: usize,
+}
+
+impl AsidAllocator {
+    /// 在当前这一代中分配一个 ASID，已经分完则进入下一代
+    ///
+    /// 返回代数和 ASID 合在一起的值（见 [`Asid`]）
+    fn alloc(&mut self) -> usize {
+        let mut generation = GENERATION.load(Ordering::Acquire);
+        if self.next == *ASID_COUNT {
+            self.next = 1;
+            // 上一代的 ASID 将被重新使用，所有 hart 都要刷新 TLB。
+            // 先标记再进入下一代，这样看到新一代的 hart 一定也看到了标记
+            STALE_HARTS.fetch_or((1 << MAX_HART_COUNT) - 1, Ordering::AcqRel);
+            generation += 1;
+            GENERATION.store(generation, Ordering::Release);
+        }
+        let asid = self.next;
+        self.next += 1;
+        (generation << ASID_MAX_BITS) | asid
+    }
+}
+
+/// 一个映射所使用的 ASID，需要时（初次激活或者进入下一代之后）才分配
+///
+/// 代数和 ASID 合在一个原子变量中，低 16 位为 ASID，其余为代数，代数为 0 表示尚未分配。
+/// ASID 在当前这一代中有效时不需要加锁，因此同一进程的线程之间切换不会在各个 hart 之间互相等待
+#[derive(Default)]
+pub struct Asid {
+    /// 代数和 ASID
+    value: AtomicUsize,
+}
+
+impl Asid {
+    /// 获得当前这一代中有效的 ASID，以及当前 hart 在使用它之前是否需要刷新整个 TLB
+    ///
+    /// 硬件不支持 ASID 时总是返回 0，并且每次都需要刷新
+    pub fn get(&self) -> (usize, bool) {
+        if *ASID_COUNT == 1 {
+            return (0, true);
+        }
+        let mut value = self.value.load(Ordering::Acquire);
+        if value >> ASID_MAX_BITS != GENERATION.load(Ordering::Acquire) {
+            // 持有锁之后再次检查，其他 hart 可能已经为同一个映射分配过了
+            let mut allocator = ASID_ALLOCATOR.lock();
+            value = self.value.load(Ordering::Acquire);
+            if value >> ASID_MAX_BITS != GENERATION.load(Ordering::Acquire) {
+                value = allocator.alloc();
+                self.value.store(value, Ordering::Release);
+            }
+        }
+        let this_hart = 1 << hart_id();
+        // 通常不需要刷新，先读取再清除，避免每次切换都写入各个 hart 共享的变量
+        let stale = STALE_HARTS.load(Ordering::Acquire) & this_hart != 0
+            && STALE_HARTS.fetch_and(!this_hart, Ordering::AcqRel) & this_hart != 0;
+        (value & ((1 << ASID_MAX_BITS) - 1), stale)
+    }
+}
+
+/// 硬件是否支持 ASID
+pub fn is_supported() -> bool {
+    *ASID_COUNT > 1
+}
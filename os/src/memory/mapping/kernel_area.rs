@@ -0,0 +1,83 @@
+//! 所有映射共享的内核区域 [`KERNEL_AREA`]
+//!
+//! 根页表中 [`KERNEL_AREA_START`] 所在的一项（第 509 项，共 1G）在所有 [`Mapping`] 中都指向同一个二级页表，
+//! 因此在这一区域中建立的映射对所有进程都可见，而不需要逐个修改各进程的页表。线程的内核栈就分配在这里。
+//!
+//! [`Mapping`]: super::Mapping
+
+use crate::memory::{
+    address::*,
+    config::KERNEL_AREA_START,
+    frame::FRAME_ALLOCATOR,
+    mapping::{Flags, Mapping, PageTable, PageTableEntry, PageTableTracker},
+    MemoryResult,
+};
+use alloc::{vec, vec::Vec};
+use lazy_static::*;
+use spin::Mutex;
+
+lazy_static! {
+    /// 共享的内核区域
+    pub static ref KERNEL_AREA: Mutex<KernelArea> =
+        Mutex::new(KernelArea::new().expect("failed to allocate kernel area"));
+}
+
+/// 共享的内核区域，维护其中的页表
+pub struct KernelArea {
+    /// 区域所用的页表，第一个为二级页表
+    page_tables: Vec<PageTableTracker>,
+}
+
+impl KernelArea {
+    /// 分配区域的二级页表
+    fn new() -> MemoryResult<Self> {
+        Ok(Self {
+            page_tables: vec![PageTableTracker::new(FRAME_ALLOCATOR.lock().alloc()?)],
+        })
+    }
+
+    /// 在根页表中写入指向这一区域的页表项
+    pub fn share_with(&self, root_table: &mut PageTable) {
+        let index = VirtualPageNumber::floor(KERNEL_AREA_START).levels()[0];
+        root_table.entries[index] =
+            PageTableEntry::new(Some(self.page_tables[0].page_number()), Flags::VALID);
+    }
+
+    /// 为给定的虚拟 / 物理页号建立映射关系
+    pub fn map(
+        &mut self,
+        vpn: VirtualPageNumber,
+        ppn: PhysicalPageNumber,
+        flags: Flags,
+    ) -> MemoryResult<()> {
+        let entry = self.find_entry(vpn)?;
+        assert!(entry.is_empty(), "virtual address is already mapped");
+        *entry = PageTableEntry::new(Some(ppn), flags);
+        Ok(())
+    }
+
+    /// 移除虚拟页号的映射，并刷新所有 hart 中这一页的 TLB
+    pub fn unmap(&mut self, vpn: VirtualPageNumber) {
+        self.find_entry(vpn).unwrap().clear();
+        Mapping::flush_tlb(Some(VirtualAddress::from(vpn)));
+    }
+
+    /// 找到给定虚拟页号的三级页表项，如果三级页表不存在则会创建
+    fn find_entry(&mut self, vpn: VirtualPageNumber) -> MemoryResult<&'static mut PageTableEntry> {
+        assert_eq!(
+            vpn.levels()[0],
+            VirtualPageNumber::floor(KERNEL_AREA_START).levels()[0],
+            "address is not in kernel area"
+        );
+        let table: &mut PageTable =
+            PhysicalAddress::from(self.page_tables[0].page_number()).deref_kernel();
+        let entry = &mut table.entries[vpn.levels()[1]];
+        if entry.is_empty() {
+            // 分配一个新的三级页表
+            let new_table = PageTableTracker::new(FRAME_ALLOCATOR.lock().alloc()?);
+            *entry = PageTableEntry::new(Some(new_table.page_number()), Flags::VALID);
+            self.page_tables.push(new_table);
+        }
+        Ok(&mut entry.get_next_table().entries[vpn.levels()[2]])
+    }
+}
@@ -3,17 +3,41 @@
 //! 许多方法返回 [`Result`]，如果出现错误会返回 `Err(message)`。设计目标是，此时如果终止线程，则不会产生后续问题。
 //! 但是如果错误是由操作系统代码逻辑产生的，则会直接 panic。
 
+use crate::hart;
 use crate::memory::{
     address::*,
     config::PAGE_SIZE,
     frame::{FrameTracker, FRAME_ALLOCATOR},
-    mapping::{Flags, MapType, PageTable, PageTableEntry, PageTableTracker, Segment},
+    mapping::{
+        asid::{self, Asid, ASID_SHIFT},
+        Flags, MapType, PageTable, PageTableEntry, PageTableTracker, Segment, KERNEL_AREA,
+        KERNEL_MAPPING,
+    },
+    range::Range,
+    swap::SWAP,
     MemoryResult,
 };
-use alloc::{collections::VecDeque, vec, vec::Vec};
+use crate::sbi::remote_sfence_vma;
+use algorithm::{Replacer, ReplacerImpl};
+use alloc::{collections::BTreeMap, sync::Arc, vec, vec::Vec};
 use core::cmp::min;
 use core::ptr::slice_from_raw_parts_mut;
 
+/// 根页表中属于内核部分（虚拟地址的高半部分）的页表项
+const KERNEL_ROOT_ENTRIES: core::ops::Range<usize> = 256..512;
+
+/// `satp` 中的模式，8 表示 Sv39
+const SATP_MODE_SV39: usize = 8 << 60;
+
+/// `satp` 中物理页号所占的位
+const SATP_PPN_MASK: usize = (1 << ASID_SHIFT) - 1;
+
+/// 按页刷新 TLB 的最大页数，超过时刷新全部
+const FLUSH_PAGES_LIMIT: usize = 64;
+
+/// 各级页表项所映射的页数：根页表中的大页为 1G，第二级页表中的大页为 2M，第三级页表中为 4K
+const LEVEL_PAGES: [usize; 3] = [1 << 18, 1 << 9, 1];
+
 #[derive(Default)]
 /// 某个线程的内存映射关系
 pub struct Mapping {
@@ -22,42 +46,155 @@ pub struct Mapping {
     /// 根页表的物理页号
     root_ppn: PhysicalPageNumber,
     /// 所有分配的物理页面映射信息
-    mapped_pairs: VecDeque<(VirtualPageNumber, FrameTracker)>,
+    ///
+    /// 物理页使用引用计数，可以在多个映射之间共享（写时复制）
+    mapped_pairs: BTreeMap<VirtualPageNumber, Arc<FrameTracker>>,
+    /// 页面置换器，物理页不足时从中选择页面换出到交换区
+    replacer: ReplacerImpl<VirtualPageNumber>,
+    /// 激活时使用的 ASID
+    asid: Asid,
 }
 
 impl Mapping {
     /// 将当前的映射加载到 `satp` 寄存器并记录
+    ///
+    /// `satp` 中带有映射的 ASID，TLB 中其他映射的项不会被误用，因此只有 ASID 进入新的一代之后才刷新整个 TLB。
+    /// 同一进程的线程之间切换时，当前 hart 已经以有效的 ASID 加载了这个映射，此时跳过
     pub fn activate(&self) {
-        // satp 低 27 位为页号，高 4 位为模式，8 表示 Sv39
-        let new_satp = self.root_ppn.0 | (8 << 60);
+        let (asid, flush) = self.asid.get();
+        // satp 低 44 位为页号，之后 16 位为 ASID，高 4 位为模式
+        let new_satp = self.root_ppn.0 | (asid << ASID_SHIFT) | SATP_MODE_SV39;
+        let satp: usize;
+        unsafe { llvm_asm!("csrr $0, satp" : "=r"(satp) ::: "volatile") };
+        if satp == new_satp && !flush {
+            return;
+        }
         unsafe {
             // 将 new_satp 的值写到 satp 寄存器
             llvm_asm!("csrw satp, $0" :: "r"(new_satp) :: "volatile");
-            // 刷新 TLB
-            llvm_asm!("sfence.vma" :::: "volatile");
+            if flush {
+                // 刷新 TLB
+                llvm_asm!("sfence.vma" :::: "volatile");
+            }
+        }
+    }
+
+    /// 切换到启动时的页表（`entry.asm` 中的 `boot_page_table`），其中只有内核的线性映射
+    ///
+    /// 启动页表使用保留的 ASID 0，且内容不会改变，因此切换时不需要刷新 TLB（除非硬件不支持 ASID）。
+    /// 调度器在空闲之前使用它，这样当前 hart 不会再引用已经释放的页表
+    pub fn activate_boot() {
+        extern "C" {
+            /// `entry.asm` 中的启动页表
+            fn boot_page_table();
+        }
+        let boot_page_table = PhysicalAddress::from(VirtualAddress(boot_page_table as usize));
+        let root_ppn = PhysicalPageNumber::floor(boot_page_table);
+        let new_satp = root_ppn.0 | SATP_MODE_SV39;
+        unsafe {
+            llvm_asm!("csrw satp, $0" :: "r"(new_satp) :: "volatile");
+            if !asid::is_supported() {
+                llvm_asm!("sfence.vma" :::: "volatile");
+            }
         }
     }
 
-    /// 创建一个有根节点的映射
+    /// 修改页表项之后，刷新所有 hart 的 TLB：`address` 为 `None` 时刷新全部，否则只刷新这一页
+    ///
+    /// 其他 hart 可能正在使用同一个页表（同一进程的其他线程，或者共享的内核区域），需要通过 SBI 一并刷新
+    pub fn flush_tlb(address: Option<VirtualAddress>) {
+        let other_harts = hart::other_harts();
+        match address {
+            Some(address) => {
+                unsafe { llvm_asm!("sfence.vma $0" :: "r"(address.0) :: "volatile") };
+                if other_harts != 0 {
+                    remote_sfence_vma(other_harts, address.0, PAGE_SIZE);
+                }
+            }
+            None => {
+                unsafe { llvm_asm!("sfence.vma" :::: "volatile") };
+                if other_harts != 0 {
+                    // 大小为 -1 表示刷新全部
+                    remote_sfence_vma(other_harts, 0, usize::MAX);
+                }
+            }
+        }
+    }
+
+    /// 移除映射之后，只刷新所有 hart 上 `range` 中各页的 TLB
+    ///
+    /// 同一个映射在各个 hart 上加载时可能使用过不同代的 ASID，因此按地址刷新而不指定 ASID。
+    /// 页数超过 [`FLUSH_PAGES_LIMIT`] 时逐页刷新反而更慢，改为刷新全部
+    fn flush_pages(range: Range<VirtualPageNumber>) {
+        if range.len() > FLUSH_PAGES_LIMIT {
+            Self::flush_tlb(None);
+            return;
+        }
+        for vpn in range.iter() {
+            let address = VirtualAddress::from(vpn);
+            unsafe { llvm_asm!("sfence.vma $0" :: "r"(address.0) :: "volatile") };
+        }
+        let other_harts = hart::other_harts();
+        if other_harts != 0 && range.len() > 0 {
+            let start = VirtualAddress::from(range.start);
+            remote_sfence_vma(other_harts, start.0, range.len() * PAGE_SIZE);
+        }
+    }
+
+    /// 创建一个有根节点的映射，其中已经包含共享的内核部分
+    ///
+    /// 内核部分的根页表项从 [`static@KERNEL_MAPPING`] 复制而来，指向同一组页表
     pub fn new() -> MemoryResult<Mapping> {
-        let root_table = PageTableTracker::new(FRAME_ALLOCATOR.lock().alloc()?);
+        let mut root_table = PageTableTracker::new(FRAME_ALLOCATOR.lock().alloc()?);
+        let kernel_table: &PageTable =
+            PhysicalAddress::from(KERNEL_MAPPING.root_ppn).deref_kernel();
+        root_table.entries[KERNEL_ROOT_ENTRIES]
+            .copy_from_slice(&kernel_table.entries[KERNEL_ROOT_ENTRIES]);
+        Ok(Self::with_root(root_table))
+    }
+
+    /// 创建 [`static@KERNEL_MAPPING`]，其中只包含共享的内核区域，之后再加入内核的各个字段
+    pub fn new_kernel() -> MemoryResult<Mapping> {
+        let mut root_table = PageTableTracker::new(FRAME_ALLOCATOR.lock().alloc()?);
+        KERNEL_AREA.lock().share_with(&mut root_table);
+        Ok(Self::with_root(root_table))
+    }
+
+    /// 以 `root_table` 为根页表创建映射
+    fn with_root(root_table: PageTableTracker) -> Mapping {
         let root_ppn = root_table.page_number();
-        Ok(Mapping {
+        Mapping {
             page_tables: vec![root_table],
             root_ppn,
-            mapped_pairs: VecDeque::new(),
-        })
+            mapped_pairs: BTreeMap::new(),
+            replacer: ReplacerImpl::default(),
+            asid: Asid::default(),
+        }
     }
 
     /// 加入一段映射，可能会相应地分配物理页面
     ///
+    /// 线性映射中对齐的部分使用 2M 或 1G 的大页，以减少页表的数量。
     /// 未被分配物理页面的虚拟页号暂时不会写入页表当中，它们会在发生 PageFault 后再建立页表项。
     pub fn map(&mut self, segment: &Segment, init_data: Option<&[u8]>) -> MemoryResult<()> {
         match segment.map_type {
             // 线性映射，直接对虚拟地址进行转换
             MapType::Linear => {
-                for vpn in segment.page_range().iter() {
-                    self.map_one(vpn, Some(vpn.into()), segment.flags | Flags::VALID)?;
+                let range = segment.page_range();
+                let mut vpn = range.start;
+                while vpn < range.end {
+                    // 虚拟页号和物理页号都对齐、剩余长度也足够时，使用尽量大的页
+                    let ppn = PhysicalPageNumber::from(vpn);
+                    let level = (0..LEVEL_PAGES.len())
+                        .find(|&level| {
+                            let pages = LEVEL_PAGES[level];
+                            vpn.0 % pages == 0 && ppn.0 % pages == 0 && range.end - vpn >= pages
+                        })
+                        .unwrap();
+                    let entry = self.find_entry_at(vpn, level)?;
+                    assert!(entry.is_empty(), "virtual address is already mapped");
+                    *entry = PageTableEntry::new(Some(ppn), segment.flags | Flags::VALID);
+                    vpn += LEVEL_PAGES[level];
                 }
                 // 拷贝数据
                 if let Some(data) = init_data {
@@ -68,71 +205,173 @@ impl Mapping {
                 }
             }
             // 需要分配帧进行映射
-            MapType::Framed => {
+            MapType::Framed | MapType::Shared => {
                 for vpn in segment.page_range().iter() {
-                    // 页面的数据，默认为全零
-                    let mut page_data = [0u8; PAGE_SIZE];
-                    // 如果提供了数据，则使用这些数据来填充 page_data
-                    if let Some(init_data) = init_data {
-                        if !init_data.is_empty() {
-                            // 这里必须进行一些调整，因为传入的数据可能并非按照整页对齐
-
-                            // 拷贝时必须考虑区间与整页不对齐的情况
-                            //    start（仅第一页时非零）
-                            //      |        stop（仅最后一页时非零）
-                            // 0    |---data---|          4096
-                            // |------------page------------|
-                            let page_address = VirtualAddress::from(vpn);
-                            let start = if segment.range.start > page_address {
-                                segment.range.start - page_address
-                            } else {
-                                0
-                            };
-                            let stop = min(PAGE_SIZE, segment.range.end - page_address);
-                            // 计算来源和目标区间并进行拷贝
-                            let dst_slice = &mut page_data[start..stop];
-                            let src_slice = &init_data[(page_address + start - segment.range.start)
-                                ..(page_address + stop - segment.range.start)];
-                            dst_slice.copy_from_slice(src_slice);
-                        }
-                    };
-
-                    // 建立映射
-                    let mut frame = FRAME_ALLOCATOR.lock().alloc()?;
-                    // 更新页表
-                    self.map_one(vpn, Some(frame.page_number()), segment.flags)?;
-                    // 写入数据
-                    (*frame).copy_from_slice(&page_data);
-                    // 保存
-                    self.mapped_pairs.push_back((vpn, frame));
+                    self.map_page(segment, vpn, init_data)?;
                 }
             }
+            // 按需分配，此时不建立任何页表项，页面在第一次访问时由 [`Mapping::map_page`] 分配
+            MapType::Lazy => {}
+        }
+        Ok(())
+    }
+
+    /// 为 `segment` 中的一页分配物理页面并建立映射
+    ///
+    /// `init_data` 是整个字段的初始数据，只会拷贝其中属于这一页的部分，其余部分填充为零
+    pub fn map_page(
+        &mut self,
+        segment: &Segment,
+        vpn: VirtualPageNumber,
+        init_data: Option<&[u8]>,
+    ) -> MemoryResult<()> {
+        // 页面的数据，默认为全零
+        let mut page_data = [0u8; PAGE_SIZE];
+        // 如果提供了数据，则使用这些数据来填充 page_data
+        if let Some(init_data) = init_data {
+            // 这里必须进行一些调整，因为传入的数据可能并非按照整页对齐
+
+            // 拷贝时必须考虑区间与整页不对齐的情况
+            //    start（仅第一页时非零）
+            //      |        stop（仅最后一页时非零）
+            // 0    |---data---|          4096
+            // |------------page------------|
+            let page_address = VirtualAddress::from(vpn);
+            let start = if segment.range.start > page_address {
+                segment.range.start - page_address
+            } else {
+                0
+            };
+            let stop = min(PAGE_SIZE, segment.range.end - page_address);
+            // 数据可能比字段短（例如 .bss），超出的部分保持为零
+            let offset = page_address + start - segment.range.start;
+            if offset < init_data.len() {
+                let length = min(stop - start, init_data.len() - offset);
+                // 计算来源和目标区间并进行拷贝
+                page_data[start..start + length]
+                    .copy_from_slice(&init_data[offset..offset + length]);
+            }
         }
+
+        // 建立映射
+        let mut frame = self.alloc_frame()?;
+        // 更新页表
+        self.map_one(vpn, Some(frame.page_number()), segment.flags)?;
+        // 写入数据
+        (*frame).copy_from_slice(&page_data);
+        // 保存，共享的页面不会被换出，不加入置换器
+        self.mapped_pairs.insert(vpn, Arc::new(frame));
+        if segment.map_type != MapType::Shared {
+            self.replacer.add_page(vpn);
+        }
+        Ok(())
+    }
+
+    /// 虚拟页是否已经分配了物理页面（且没有被换出）
+    pub fn is_mapped(&self, vpn: VirtualPageNumber) -> bool {
+        self.mapped_pairs.contains_key(&vpn)
+    }
+
+    /// 虚拟页是否已经被换出到交换区
+    pub fn is_swapped(&self, vpn: VirtualPageNumber) -> bool {
+        Self::walk(self.root_ppn, vpn)
+            .and_then(|entry| entry.swap_slot())
+            .is_some()
+    }
+
+    /// 将被换出的页面读回内存，页表项恢复为换出前的权限
+    pub fn swap_in(&mut self, vpn: VirtualPageNumber) -> MemoryResult<()> {
+        let entry = Self::walk(self.root_ppn, vpn).ok_or("page is not swapped out")?;
+        let slot = entry.swap_slot().ok_or("page is not swapped out")?;
+        let flags = entry.flags();
+        // 分配物理页时可能换出其他页面
+        let mut frame = self.alloc_frame()?;
+        SWAP.lock().read_page(slot, &mut frame)?;
+        // 更新页表
+        let entry = Self::walk(self.root_ppn, vpn).unwrap();
+        *entry = PageTableEntry::new(Some(frame.page_number()), flags);
+        // 保存
+        self.mapped_pairs.insert(vpn, Arc::new(frame));
+        self.replacer.add_page(vpn);
         Ok(())
     }
 
     /// 移除一段映射
+    ///
+    /// 只访问已经存在的页表，不会分配页表：按需分配的字段中尚未访问过的页面没有页表项，会被跳过；
+    /// 已被换出的页面会释放其在交换区中的位置。线性映射中的大页由同一字段建立，整个位于字段之内，直接清除
     pub fn unmap(&mut self, segment: &Segment) {
-        for vpn in segment.page_range().iter() {
-            let entry = self.find_entry(vpn).unwrap();
-            assert!(!entry.is_empty());
+        Self::for_each_entry(self.root_ppn, segment.page_range(), |_, entry| {
+            if let Some(slot) = entry.swap_slot() {
+                SWAP.lock().free(slot);
+            }
             // 从页表中清除项
             entry.clear();
-        }
+        });
+        Self::flush_pages(segment.page_range());
         // 移除相应的页面
-        self.mapped_pairs
-            .retain(|(vpn, _)| !segment.page_range().contains(*vpn))
+        let range = segment.page_range();
+        let vpns: Vec<VirtualPageNumber> = self
+            .mapped_pairs
+            .range(range.start..range.end)
+            .map(|(vpn, _)| *vpn)
+            .collect();
+        for vpn in vpns {
+            self.mapped_pairs.remove(&vpn);
+            self.replacer.remove_page(&vpn);
+        }
+    }
+
+    /// 对以 `root_ppn` 为根的页表中 `range` 内每个已经存在的末级页表项（可能是大页）调用 `f`
+    ///
+    /// 不存在的页表整个跳过，因此访问很大而几乎没有使用的区间也很快，且不会分配页表
+    fn for_each_entry(
+        root_ppn: PhysicalPageNumber,
+        range: Range<VirtualPageNumber>,
+        mut f: impl FnMut(VirtualPageNumber, &mut PageTableEntry),
+    ) {
+        let root_table: &mut PageTable = PhysicalAddress::from(root_ppn).deref_kernel();
+        let mut vpn = range.start;
+        while vpn < range.end {
+            // 向下查找，直到末级页表项、大页或者不存在的页表
+            let levels = vpn.levels();
+            let mut level = 0;
+            let mut entry = &mut root_table.entries[levels[0]];
+            while level + 1 < LEVEL_PAGES.len() && !entry.is_empty() && entry.has_next_level() {
+                level += 1;
+                entry = &mut entry.get_next_table().entries[levels[level]];
+            }
+            if !entry.is_empty() {
+                f(vpn, entry);
+            }
+            // 跳到这个页表项所覆盖的范围之后
+            let pages = LEVEL_PAGES[level];
+            vpn = VirtualPageNumber(vpn.0 - vpn.0 % pages + pages);
+        }
     }
 
     /// 找到给定虚拟页号的三级页表项
     ///
-    /// 如果找不到对应的页表项，则会相应创建页表
+    /// 如果找不到对应的页表项，则会相应创建页表；途经的大页会被拆分
     pub fn find_entry(&mut self, vpn: VirtualPageNumber) -> MemoryResult<&mut PageTableEntry> {
+        self.find_entry_at(vpn, LEVEL_PAGES.len() - 1)
+    }
+
+    /// 找到给定虚拟页号在第 `level` 级页表（0 为根页表）中的页表项
+    ///
+    /// 如果找不到对应的页表项，则会相应创建页表。如果途经的页表项是更大的页，则将其拆分为
+    /// 下一级页表中的 512 个页表项，映射的物理地址和标志位不变，因此不需要刷新 TLB
+    fn find_entry_at(
+        &mut self,
+        vpn: VirtualPageNumber,
+        level: usize,
+    ) -> MemoryResult<&mut PageTableEntry> {
         // 从根页表开始向下查询
         // 这里不用 self.page_tables[0] 避免后面产生 borrow-check 冲突（我太菜了）
         let root_table: &mut PageTable = PhysicalAddress::from(self.root_ppn).deref_kernel();
-        let mut entry = &mut root_table.entries[vpn.levels()[0]];
-        for vpn_slice in &vpn.levels()[1..] {
+        let levels = vpn.levels();
+        let mut entry = &mut root_table.entries[levels[0]];
+        for depth in 1..=level {
             if entry.is_empty() {
                 // 如果页表不存在，则需要分配一个新的页表
                 let new_table = PageTableTracker::new(FRAME_ALLOCATOR.lock().alloc()?);
@@ -141,20 +380,161 @@ impl Mapping {
                 *entry = PageTableEntry::new(Some(new_ppn), Flags::VALID);
                 // 保存页表
                 self.page_tables.push(new_table);
+            } else if !entry.has_next_level() {
+                // 大页，拆分到新的下一级页表中
+                let mut new_table = PageTableTracker::new(FRAME_ALLOCATOR.lock().alloc()?);
+                let (ppn, flags) = (entry.page_number(), entry.flags());
+                for (i, child) in new_table.entries.iter_mut().enumerate() {
+                    *child = PageTableEntry::new(Some(ppn + i * LEVEL_PAGES[depth]), flags);
+                }
+                *entry = PageTableEntry::new(Some(new_table.page_number()), Flags::VALID);
+                self.page_tables.push(new_table);
             }
             // 进入下一级页表（使用偏移量来访问物理地址）
-            entry = &mut entry.get_next_table().entries[*vpn_slice];
+            entry = &mut entry.get_next_table().entries[levels[depth]];
         }
-        // 此时 entry 位于第三级页表
         Ok(entry)
     }
 
+    /// 以写时复制的方式，将 `source` 中 `segment` 的所有页面共享到当前映射中
+    ///
+    /// 双方共享同一组物理页，可写的页面在双方的页表中都会去掉可写标志。
+    /// 之后任何一方写入时会发生缺页异常，再由 [`Mapping::copy_on_write`] 复制出私有的页面。
+    ///
+    /// [`MapType::Shared`] 的字段则直接共享可写的物理页，不会写时复制
+    pub fn map_shared(&mut self, segment: &Segment, source: &mut Mapping) -> MemoryResult<()> {
+        if segment.map_type == MapType::Shared {
+            for vpn in segment.page_range().iter() {
+                let frame = source.mapped_pairs[&vpn].clone();
+                self.map_one(vpn, Some(frame.page_number()), segment.flags)?;
+                self.mapped_pairs.insert(vpn, frame);
+            }
+            return Ok(());
+        }
+        let flags = segment.flags - Flags::WRITABLE;
+        for vpn in segment.page_range().iter() {
+            // 被换出的页面先读回内存再共享
+            if source.is_swapped(vpn) {
+                source.swap_in(vpn)?;
+            }
+            let frame = match source.mapped_pairs.get(&vpn) {
+                Some(frame) => frame.clone(),
+                // 按需分配而尚未访问的页面，双方之后各自分配
+                None if segment.map_type == MapType::Lazy => continue,
+                None => panic!("page to share is not mapped in source"),
+            };
+            // 源映射中的页面同样变为只读
+            if segment.flags.contains(Flags::WRITABLE) {
+                let entry = source.find_entry(vpn)?;
+                entry.set_flags(entry.flags() - Flags::WRITABLE);
+            }
+            // 更新页表
+            self.map_one(vpn, Some(frame.page_number()), flags)?;
+            // 保存
+            self.mapped_pairs.insert(vpn, frame);
+            self.replacer.add_page(vpn);
+        }
+        // 源映射可能正在使用，需要刷新 TLB 使只读标志生效
+        Self::flush_tlb(None);
+        Ok(())
+    }
+
+    /// 将 `segment` 中已经建立的页表项的权限改为 `segment.flags`
+    ///
+    /// 仍在写时复制的页面保持只读，之后写入时由 [`Mapping::copy_on_write`] 恢复为 `segment.flags`；
+    /// 已被换出的页面同样修改，换入时使用新的权限
+    pub fn protect(&mut self, segment: &Segment) {
+        let mapped_pairs = &self.mapped_pairs;
+        Self::for_each_entry(self.root_ppn, segment.page_range(), |vpn, entry| {
+            let kept = entry.flags() & (Flags::VALID | Flags::ACCESSED | Flags::DIRTY);
+            let mut flags = segment.flags | kept;
+            let copy_on_write = match mapped_pairs.get(&vpn) {
+                Some(frame) => Arc::strong_count(frame) > 1,
+                None => false,
+            };
+            if copy_on_write && segment.map_type != MapType::Shared {
+                flags -= Flags::WRITABLE;
+            }
+            entry.set_flags(flags);
+        });
+        Self::flush_pages(segment.page_range());
+    }
+
+    /// 通过物理页读取从 `start` 开始的内容，不经过当前的页表，也不会引起缺页异常
+    ///
+    /// 只能用于已经分配且没有被换出的页面，例如 [`MapType::Shared`] 的字段
+    pub fn read_bytes(&self, start: VirtualAddress, buffer: &mut [u8]) -> MemoryResult<()> {
+        let mut copied = 0;
+        while copied < buffer.len() {
+            let address = start + copied;
+            let frame = self
+                .mapped_pairs
+                .get(&VirtualPageNumber::floor(address))
+                .ok_or("page is not mapped by frame")?;
+            let offset = address.page_offset();
+            let size = min(PAGE_SIZE - offset, buffer.len() - copied);
+            buffer[copied..copied + size].copy_from_slice(&frame[offset..offset + size]);
+            copied += size;
+        }
+        Ok(())
+    }
+
+    /// 通过物理页写入从 `start` 开始的内容，不经过当前的页表，也不会引起缺页异常
+    ///
+    /// 只能用于已经分配的页面，被换出的页面会先读回。物理页与其他映射共享时，写入的内容对它们同样可见
+    pub fn write_bytes(&mut self, start: VirtualAddress, data: &[u8]) -> MemoryResult<()> {
+        let mut copied = 0;
+        while copied < data.len() {
+            let address = start + copied;
+            let vpn = VirtualPageNumber::floor(address);
+            if self.is_swapped(vpn) {
+                self.swap_in(vpn)?;
+            }
+            let frame = self
+                .mapped_pairs
+                .get(&vpn)
+                .ok_or("page is not mapped by frame")?;
+            let page = frame.page_number().deref_kernel();
+            let offset = address.page_offset();
+            let size = min(PAGE_SIZE - offset, data.len() - copied);
+            page[offset..offset + size].copy_from_slice(&data[copied..copied + size]);
+            copied += size;
+        }
+        Ok(())
+    }
+
+    /// 处理对写时复制页面的写入
+    ///
+    /// 如果物理页仍与其他映射共享，则分配新的物理页并拷贝数据；否则直接使用原来的物理页。
+    /// 之后将页表项恢复为 `flags` 所指定的权限。
+    pub fn copy_on_write(&mut self, vpn: VirtualPageNumber, flags: Flags) -> MemoryResult<()> {
+        let frame = self
+            .mapped_pairs
+            .get(&vpn)
+            .ok_or("page is not mapped by frame")?;
+        if Arc::strong_count(frame) > 1 {
+            // 仍然被共享，复制出一个私有的物理页。分配时可能需要换出页面，暂时将这一页移出置换器以免被换出
+            self.replacer.remove_page(&vpn);
+            let new_frame = self.alloc_frame();
+            self.replacer.add_page(vpn);
+            let mut new_frame = new_frame?;
+            let frame = self.mapped_pairs.get_mut(&vpn).unwrap();
+            (*new_frame).copy_from_slice(&***frame);
+            *frame = Arc::new(new_frame);
+        }
+        let ppn = self.mapped_pairs[&vpn].page_number();
+        // 更新页表项并刷新这一页的 TLB
+        *self.find_entry(vpn)? = PageTableEntry::new(Some(ppn), flags);
+        Self::flush_tlb(Some(VirtualAddress::from(vpn)));
+        Ok(())
+    }
+
     /// 查找虚拟地址对应的物理地址
     pub fn lookup(va: VirtualAddress) -> Option<PhysicalAddress> {
         let mut current_ppn;
         unsafe {
             llvm_asm!("csrr $0, satp" : "=r"(current_ppn) ::: "volatile");
-            current_ppn ^= 8 << 60;
+            current_ppn &= SATP_PPN_MASK;
         }
 
         let root_table: &PageTable =
@@ -174,11 +554,77 @@ impl Mapping {
                 break;
             }
         }
+        // 尚未分配或已被换出的页面没有有效的页表项
+        if !entry.flags().contains(Flags::VALID) {
+            return None;
+        }
         let base = PhysicalAddress::from(entry.page_number()).0;
         let offset = va.0 & ((1 << length) - 1);
         Some(PhysicalAddress(base + offset))
     }
 
+    /// 分配一个物理页，物理页不足时从当前映射中换出一个页面
+    fn alloc_frame(&mut self) -> MemoryResult<FrameTracker> {
+        loop {
+            let frame = FRAME_ALLOCATOR.lock().alloc();
+            if frame.is_ok() {
+                return frame;
+            }
+            self.swap_out()?;
+        }
+    }
+
+    /// 由置换器选择一个页面，将其写入交换区并释放物理页
+    fn swap_out(&mut self) -> MemoryResult<()> {
+        let root_ppn = self.root_ppn;
+        let mapped_pairs = &self.mapped_pairs;
+        let vpn = self
+            .replacer
+            .choose_victim(|vpn| {
+                // 读取并清除已访问位
+                let entry = Self::walk(root_ppn, *vpn).unwrap();
+                let accessed = entry.flags().contains(Flags::ACCESSED);
+                entry.set_flags(entry.flags() - Flags::ACCESSED);
+                // 与其他映射共享的页面换出后并不能释放物理页，尽量不选择它们
+                accessed || Arc::strong_count(&mapped_pairs[vpn]) > 1
+            })
+            .ok_or("no available frame to allocate")?;
+        let frame = self.mapped_pairs.remove(&vpn).unwrap();
+        let slot = match SWAP.lock().write_page(&frame) {
+            Ok(slot) => slot,
+            Err(msg) => {
+                // 无法换出，恢复原状
+                self.mapped_pairs.insert(vpn, frame);
+                self.replacer.add_page(vpn);
+                return Err(msg);
+            }
+        };
+        // 页表项记录交换区中的位置
+        let entry = Self::walk(root_ppn, vpn).unwrap();
+        *entry = PageTableEntry::new_swapped(slot, entry.flags());
+        // 只刷新被换出的页面。其他页面清除的已访问位在 TLB 中可能暂时不生效，只影响置换的准确性
+        Self::flush_pages(Range::from(vpn..vpn + 1));
+        Ok(())
+    }
+
+    /// 在以 `root_ppn` 为根的页表中找到虚拟页号的三级页表项
+    ///
+    /// 页表不存在，或者虚拟页位于大页之中时返回 `None`
+    fn walk(
+        root_ppn: PhysicalPageNumber,
+        vpn: VirtualPageNumber,
+    ) -> Option<&'static mut PageTableEntry> {
+        let root_table: &mut PageTable = PhysicalAddress::from(root_ppn).deref_kernel();
+        let mut entry = &mut root_table.entries[vpn.levels()[0]];
+        for vpn_slice in &vpn.levels()[1..] {
+            if entry.is_empty() || !entry.has_next_level() {
+                return None;
+            }
+            entry = &mut entry.get_next_table().entries[*vpn_slice];
+        }
+        Some(entry)
+    }
+
     /// 为给定的虚拟 / 物理页号建立映射关系
     fn map_one(
         &mut self,
@@ -8,7 +8,8 @@ use crate::memory::{
     range::Range,
     MemoryResult,
 };
-use alloc::{vec, vec::Vec};
+use alloc::{collections::BTreeMap, sync::Arc, vec, vec::Vec};
+use lazy_static::lazy_static;
 use xmas_elf::{
     program::{SegmentData, Type},
     ElfFile,
@@ -20,11 +21,33 @@ pub struct MemorySet {
     pub mapping: Mapping,
     /// 每个字段
     pub segments: Vec<Segment>,
+    /// 按需分配字段的初始数据，以字段的起始地址为键
+    init_data: BTreeMap<VirtualAddress, Arc<[u8]>>,
+}
+
+lazy_static! {
+    /// 内核的映射，启动时建立一次
+    ///
+    /// 它的根页表中内核部分的页表项会被复制到每个新建的 [`Mapping`] 中（见 [`Mapping::new`]），
+    /// 因此所有映射共享内核部分的页表，各进程只拥有用户部分的页表
+    pub static ref KERNEL_MAPPING: Mapping =
+        MemorySet::map_kernel().expect("failed to build kernel mapping");
 }
 
 impl MemorySet {
-    /// 创建内核重映射
+    /// 创建只含有内核映射的 [`MemorySet`]
+    ///
+    /// 内核部分直接共享 [`static@KERNEL_MAPPING`] 的页表，不需要重新建立
     pub fn new_kernel() -> MemoryResult<MemorySet> {
+        Ok(MemorySet {
+            mapping: Mapping::new()?,
+            segments: Vec::new(),
+            init_data: BTreeMap::new(),
+        })
+    }
+
+    /// 建立内核重映射，只在初始化 [`static@KERNEL_MAPPING`] 时调用一次
+    fn map_kernel() -> MemoryResult<Mapping> {
         // 在 linker.ld 里面标记的各个字段的起始点，均为 4K 对齐
         extern "C" {
             fn text_start();
@@ -72,16 +95,18 @@ impl MemorySet {
                 flags: Flags::READABLE | Flags::WRITABLE,
             },
         ];
-        let mut mapping = Mapping::new()?;
+        let mut mapping = Mapping::new_kernel()?;
 
         // 每个字段在页表中进行映射
         for segment in segments.iter() {
             mapping.map(segment, None)?;
         }
-        Ok(MemorySet { mapping, segments })
+        Ok(mapping)
     }
 
     /// 通过 elf 文件创建内存映射（不包括栈）
+    ///
+    /// 各个字段都按需分配，数据会在第一次访问时从 elf 文件中拷贝
     pub fn from_elf(file: &ElfFile, is_user: bool) -> MemoryResult<MemorySet> {
         // 建立带有内核映射的 MemorySet
         let mut memory_set = MemorySet::new_kernel()?;
@@ -103,7 +128,7 @@ impl MemorySet {
 
             // 将每一部分作为 Segment 进行映射
             let segment = Segment {
-                map_type: MapType::Framed,
+                map_type: MapType::Lazy,
                 range: Range::from(start..(start + size)),
                 flags: Flags::user(is_user)
                     | Flags::readable(program_header.flags().is_read())
@@ -111,16 +136,75 @@ impl MemorySet {
                     | Flags::executable(program_header.flags().is_execute()),
             };
 
-            // 建立映射并复制数据
+            // 记录字段和数据，之后再建立映射
             memory_set.add_segment(segment, Some(data))?;
         }
 
         Ok(memory_set)
     }
 
+    /// 复制一份内存映射（用于 fork）
+    ///
+    /// 内核部分直接共享，而其他字段中已分配的页面都以写时复制的方式与原映射共享物理页
+    pub fn fork(&mut self) -> MemoryResult<MemorySet> {
+        // 建立带有内核映射的 MemorySet
+        let mut memory_set = MemorySet::new_kernel()?;
+
+        // 逐个共享用户部分的字段
+        for segment in self.segments.iter() {
+            memory_set.mapping.map_shared(segment, &mut self.mapping)?;
+            memory_set.segments.push(*segment);
+        }
+        // 尚未分配的页面仍然需要初始数据
+        memory_set.init_data = self.init_data.clone();
+
+        Ok(memory_set)
+    }
+
+    /// 处理发生在 `address` 的缺页异常，`access` 为引起异常的访问所需的权限
+    ///
+    /// 如果页面已被换出，则将其读回；如果这是对按需分配页面的第一次访问，则分配页面并填充数据；
+    /// 如果这是一次对写时复制页面的写入，则为其准备可写的页面；否则返回 `Err`，说明访问非法
+    pub fn handle_page_fault(
+        &mut self,
+        address: VirtualAddress,
+        access: Flags,
+    ) -> MemoryResult<()> {
+        let vpn = VirtualPageNumber::floor(address);
+        // 找到地址所在的字段
+        let segment = *self
+            .segments
+            .iter()
+            .find(|segment| segment.page_range().contains(vpn))
+            .ok_or("address is not mapped")?;
+        if !segment.flags.contains(access) {
+            return Err("invalid memory access");
+        }
+        if !self.mapping.is_mapped(vpn) {
+            if self.mapping.is_swapped(vpn) {
+                // 页面被换出到了交换区
+                self.mapping.swap_in(vpn)
+            } else if segment.map_type == MapType::Lazy {
+                // 按需分配的页面尚未分配
+                let init_data = self.init_data.get(&segment.range.start);
+                self.mapping.map_page(&segment, vpn, init_data.map(|data| &data[..]))
+            } else {
+                Err("address is not mapped")
+            }
+        } else if access == Flags::WRITABLE
+            && segment.map_type != MapType::Linear
+            && segment.map_type != MapType::Shared
+        {
+            // 字段可写，而页面只读，说明是写时复制的页面
+            self.mapping.copy_on_write(vpn, segment.flags)
+        } else {
+            Err("invalid memory access")
+        }
+    }
+
     /// 替换 `satp` 以激活页表
     ///
-    /// 如果当前页表就是自身，则不会替换，但仍然会刷新 TLB。
+    /// 同一进程的线程之间切换时，当前 hart 已经加载了这个页表，此时跳过（见 [`Mapping::activate`]）
     pub fn activate(&self) {
         self.mapping.activate();
     }
@@ -131,6 +215,10 @@ impl MemorySet {
         assert!(!self.overlap_with(segment.page_range()));
         // 映射并将新分配的页面保存下来
         self.mapping.map(&segment, init_data)?;
+        // 按需分配的字段需要保留数据
+        if let (MapType::Lazy, Some(data)) = (segment.map_type, init_data) {
+            self.init_data.insert(segment.range.start, Arc::from(data));
+        }
         self.segments.push(segment);
         Ok(())
     }
@@ -146,11 +234,60 @@ impl MemorySet {
             .position(|s| s == segment)
             .expect("segment to remove cannot be found");
         self.segments.remove(segment_index);
+        self.init_data.remove(&segment.range.start);
         // 移除映射
         self.mapping.unmap(segment);
         Ok(())
     }
 
+    /// 找到恰好覆盖 `[start, start + size)` 的字段，区间的结尾按页向上取整
+    pub fn find_segment(&self, start: VirtualAddress, size: usize) -> Option<Segment> {
+        let end = VirtualPageNumber::ceil(start + size);
+        self.segments
+            .iter()
+            .find(|segment| segment.range.start == start && segment.page_range().end == end)
+            .copied()
+    }
+
+    /// 修改起始于 `start` 的字段的权限，已经建立的页表项一并修改
+    pub fn protect_segment(&mut self, start: VirtualAddress, flags: Flags) -> MemoryResult<()> {
+        let segment = self
+            .segments
+            .iter_mut()
+            .find(|segment| segment.range.start == start)
+            .ok_or("segment to protect cannot be found")?;
+        segment.flags = flags;
+        let segment = *segment;
+        self.mapping.protect(&segment);
+        Ok(())
+    }
+
+    /// 移除所有字段，只保留共享的内核部分（用于进程退出）
+    pub fn clear(&mut self) {
+        for segment in self.segments.iter() {
+            self.mapping.unmap(segment);
+        }
+        self.segments.clear();
+        self.init_data.clear();
+    }
+
+    /// `range` 中的每一页是否都属于某个具有 `access` 权限的字段
+    ///
+    /// 系统调用访问用户传入的缓冲区之前用它检查，以免在持有锁时发生无法处理的缺页
+    pub fn contains_range(&self, range: Range<VirtualAddress>, access: Flags) -> bool {
+        let end = VirtualPageNumber::ceil(range.end);
+        let mut vpn = VirtualPageNumber::floor(range.start);
+        while vpn < end {
+            match self.segments.iter().find(|segment| {
+                segment.page_range().contains(vpn) && segment.flags.contains(access)
+            }) {
+                Some(segment) => vpn = segment.page_range().end,
+                None => return false,
+            }
+        }
+        true
+    }
+
     /// 检测一段内存区域和已有的是否存在重叠区域
     pub fn overlap_with(&self, range: Range<VirtualPageNumber>) -> bool {
         for seg in self.segments.iter() {
@@ -48,6 +48,28 @@ impl PageTableEntry {
                 .set_bits(PAGE_NUMBER_RANGE, 0);
         }
     }
+    /// 设置标志位，物理页号保持不变
+    pub fn set_flags(&mut self, flags: Flags) {
+        self.0.set_bits(FLAG_RANGE, flags.bits() as usize);
+    }
+    /// 生成一个被换出页面的页表项
+    ///
+    /// 页表项不包含 Valid 位，原本存放物理页号的位置用来记录交换区中的位置，其他标志位保持不变
+    pub fn new_swapped(slot: usize, flags: Flags) -> Self {
+        Self(
+            *0usize
+                .set_bits(FLAG_RANGE, (flags - Flags::VALID).bits() as usize)
+                .set_bits(PAGE_NUMBER_RANGE, slot),
+        )
+    }
+    /// 如果页面已被换出，返回其在交换区中的位置
+    pub fn swap_slot(&self) -> Option<usize> {
+        if !self.is_empty() && !self.flags().contains(Flags::VALID) {
+            Some(self.0.get_bits(PAGE_NUMBER_RANGE))
+        } else {
+            None
+        }
+    }
     /// 清除
     pub fn clear(&mut self) {
         self.0 = 0;
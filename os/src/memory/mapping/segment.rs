@@ -9,6 +9,12 @@ pub enum MapType {
     Linear,
     /// 按帧分配映射
     Framed,
+    /// 按需分配映射，在第一次访问页面时才分配帧
+    Lazy,
+    /// 共享映射，立即分配帧，且不会被换出
+    ///
+    /// fork 时与子进程共享可写的物理页，双方的写入互相可见（而不是写时复制）
+    Shared,
 }
 
 /// 一个映射片段（对应旧 tutorial 的 `MemoryArea`）
@@ -29,7 +35,7 @@ impl Segment {
             // 线性映射可以直接将虚拟地址转换
             MapType::Linear => Some(self.page_range().into().iter()),
             // 按帧映射无法直接获得物理地址，需要分配
-            MapType::Framed => None,
+            MapType::Framed | MapType::Lazy | MapType::Shared => None,
         }
     }
 
@@ -12,6 +12,7 @@ pub mod frame;
 pub mod heap;
 pub mod mapping;
 pub mod range;
+pub mod swap;
 
 /// 一个缩写，模块中一些函数会使用
 pub type MemoryResult<T> = Result<T, &'static str>;
@@ -0,0 +1,74 @@
+//! 页面交换区 [`SWAP`]
+//!
+//! 使用第二个块设备作为交换区，物理页不足时将页面换出到其中
+
+use crate::drivers::driver::{DeviceType, Driver, DRIVERS};
+use crate::memory::{config::*, frame::FrameTracker, MemoryResult};
+use algorithm::*;
+use alloc::sync::Arc;
+use lazy_static::*;
+use spin::Mutex;
+
+/// 块设备中每个块的大小
+const BLOCK_SIZE: usize = 512;
+/// 每个页面占用的块数
+const BLOCKS_PER_PAGE: usize = PAGE_SIZE / BLOCK_SIZE;
+
+lazy_static! {
+    /// 交换区
+    pub static ref SWAP: Mutex<Swap> = Mutex::new(Swap::new());
+}
+
+/// 交换区，以页面为单位分配
+pub struct Swap {
+    /// 交换区所在的块设备，没有第二个块设备时为 `None`
+    device: Option<Arc<dyn Driver>>,
+    /// 分配交换区中的位置
+    allocator: AllocatorImpl,
+}
+
+impl Swap {
+    /// 选择第二个块设备作为交换区（第一个为根文件系统）
+    fn new() -> Self {
+        let device = DRIVERS
+            .read()
+            .iter()
+            .filter(|driver| driver.device_type() == DeviceType::Block)
+            .nth(1)
+            .cloned();
+        Self {
+            device,
+            allocator: AllocatorImpl::new(SWAP_PAGE_COUNT),
+        }
+    }
+
+    /// 将页面的内容写入交换区，返回其位置
+    pub fn write_page(&mut self, frame: &FrameTracker) -> MemoryResult<usize> {
+        let device = self.device.as_ref().ok_or("no swap device")?;
+        let slot = self.allocator.alloc().ok_or("swap space exhausted")?;
+        for (i, block) in frame.chunks(BLOCK_SIZE).enumerate() {
+            if !device.write_block(slot * BLOCKS_PER_PAGE + i, block) {
+                self.allocator.dealloc(slot);
+                return Err("failed to write swap device");
+            }
+        }
+        Ok(slot)
+    }
+
+    /// 从交换区读出页面的内容，并释放其位置
+    pub fn read_page(&mut self, slot: usize, frame: &mut FrameTracker) -> MemoryResult<()> {
+        let device = self.device.as_ref().ok_or("no swap device")?;
+        for (i, block) in frame.chunks_mut(BLOCK_SIZE).enumerate() {
+            if !device.read_block(slot * BLOCKS_PER_PAGE + i, block) {
+                return Err("failed to read swap device");
+            }
+        }
+        self.allocator.dealloc(slot);
+        Ok(())
+    }
+
+    /// 释放交换区中的位置，其中的内容不再需要
+    pub fn free(&mut self, slot: usize) {
+        self.allocator.dealloc(slot);
+    }
+}
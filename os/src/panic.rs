@@ -1,9 +1,12 @@
 //! 代替 std 库，实现 panic 和 abort 的功能
 
+use crate::fs::sync_before_shutdown;
 use crate::sbi::shutdown;
 use core::panic::PanicInfo;
 
-/// 打印 panic 的信息并 [`shutdown`]
+/// 打印 panic 的信息，将文件系统的修改写回磁盘，然后 [`shutdown`]
+///
+/// 所有线程结束后也是通过 panic 关机的
 ///
 /// ### `#[panic_handler]` 属性
 /// 声明此函数是 panic 的回调
@@ -23,6 +26,7 @@ fn panic_handler(info: &PanicInfo) -> ! {
     } else {
         println!("\x1b[1;31mpanic: '{}'\x1b[0m", info.message().unwrap());
     }
+    sync_before_shutdown();
     shutdown()
 }
 
@@ -3,5 +3,11 @@
 /// 每个线程的运行栈大小 512 KB
 pub const STACK_SIZE: usize = 0x8_0000;
 
-/// 共用的内核栈大小 512 KB
-pub const KERNEL_STACK_SIZE: usize = 0x8_0000;
+/// 线程的默认优先级（见 [`algorithm::StrideScheduler`]）
+pub const DEFAULT_PRIORITY: usize = 16;
+
+/// 每个线程的内核栈大小 128 KB
+pub const KERNEL_STACK_SIZE: usize = 0x2_0000;
+
+/// 每个进程的文件描述符编号上限
+pub const MAX_DESCRIPTORS: usize = 0x100;
@@ -1,40 +1,132 @@
 //! 内核栈 [`KernelStack`]
 //!
 //! 用户态的线程出现中断时，因为用户栈无法保证可用性，中断处理流程必须在内核栈上进行。
-//! 所以我们创建一个公用的内核栈，即当发生中断时，会将 Context 写到内核栈顶。
+//! 每个线程拥有自己的内核栈，因此线程可以在内核中途休眠，之后带着完整的调用栈继续执行。
+//!
+//! ### 内核栈的位置
+//! 内核栈分配在所有进程共享的内核区域（[`KERNEL_AREA`]）中，每个栈由物理页按帧映射，
+//! 其下方留出一个不映射的保护页，栈溢出时会触发缺页异常，而不会悄悄破坏其他数据。
 //!
 //! ### 线程 [`Context`] 的存放
-//! > 1. 线程初始化时，一个 `Context` 放置在内核栈顶，`sp` 指向 `Context` 的位置
-//! >   （即栈顶 - `size_of::<Context>()`）
-//! > 2. 切换到线程，执行 `__restore` 时，将 `Context` 的数据恢复到寄存器中后，
-//! >   会将 `Context` 出栈（即 `sp += size_of::<Context>()`），
+//! > 1. 线程初始化时，一个 `Context` 放置在内核栈顶，其下是供 `__switch` 恢复的 [`SwitchContext`]，
+//! >   其返回地址为 `__restore`
+//! > 2. 第一次切换到线程时，`__switch` 恢复 [`SwitchContext`] 后返回至 `__restore`，
+//! >   此时 `sp` 指向 `Context`，`__restore` 将其恢复到寄存器中后出栈，
 //! >   然后保存 `sp` 至 `sscratch`（此时 `sscratch` 即为内核栈顶）
 //! > 3. 发生中断时，将 `sscratch` 和 `sp` 互换，入栈一个 `Context` 并保存数据
 //!
-//! 容易发现，线程的 `Context` 一定保存在内核栈顶。因此，当线程需要运行时，
-//! 从 [`Thread`] 中取出 `Context` 然后置于内核栈顶即可
+//! 容易发现，线程位于内核中时，它的 `Context` 一定保存在内核栈顶。
+//!
+//! 栈顶之上还保留了一个位置：`__restore` 返回时将当前 hart 的编号（`tp`）存放在这里，
+//! 而用户程序可能改写 `tp`，因此中断时从这里取回。
 
 use super::*;
+use crate::memory::{frame::FrameTracker, mapping::KERNEL_AREA};
+use algorithm::*;
 use core::mem::size_of;
+use lazy_static::*;
+
+lazy_static! {
+    /// 分配内核区域中的栈位置，每个位置包括一个保护页和栈本身
+    static ref KERNEL_STACK_SLOTS: Mutex<AllocatorImpl> =
+        Mutex::new(AllocatorImpl::new(KERNEL_AREA_SIZE / KERNEL_STACK_SLOT_SIZE));
+}
+
+/// 每个内核栈在内核区域中占用的大小（包括保护页）
+const KERNEL_STACK_SLOT_SIZE: usize = KERNEL_STACK_SIZE + PAGE_SIZE;
 
-/// 内核栈
-#[repr(align(16))]
+/// `__switch` 在栈上保存的寄存器：`ra` 和 `s0` 至 `s11`
 #[repr(C)]
-pub struct KernelStack([u8; KERNEL_STACK_SIZE]);
+#[derive(Default)]
+pub struct SwitchContext {
+    /// 返回地址
+    ra: usize,
+    /// 被调用者保存的寄存器
+    s: [usize; 12],
+}
 
-/// 公用的内核栈
-pub static mut KERNEL_STACK: KernelStack = KernelStack([0; KERNEL_STACK_SIZE]);
+/// 线程的内核栈
+pub struct KernelStack {
+    /// 在内核区域中的位置
+    slot: usize,
+    /// 栈所用的物理页
+    frames: Vec<FrameTracker>,
+}
 
 impl KernelStack {
-    /// 在栈顶加入 Context 并且返回新的栈顶指针
-    pub fn push_context(&mut self, context: Context) -> *mut Context {
-        // 栈顶
-        let stack_top = &self.0 as *const _ as usize + size_of::<Self>();
+    /// 分配一个内核栈，并在共享的内核区域中建立映射
+    pub fn new() -> MemoryResult<Self> {
+        let slot = KERNEL_STACK_SLOTS
+            .lock()
+            .alloc()
+            .ok_or("no available kernel stack")?;
+        let mut stack = Self {
+            slot,
+            frames: Vec::with_capacity(KERNEL_STACK_SIZE / PAGE_SIZE),
+        };
+        // 保护页之上的每一页都按帧映射（失败时 drop 会清除已建立的映射）
+        let start = VirtualPageNumber::floor(stack.bottom());
+        for i in 0..KERNEL_STACK_SIZE / PAGE_SIZE {
+            let frame = FRAME_ALLOCATOR.lock().alloc()?;
+            KERNEL_AREA.lock().map(
+                start + i,
+                frame.page_number(),
+                Flags::READABLE | Flags::WRITABLE,
+            )?;
+            stack.frames.push(frame);
+        }
+        Ok(stack)
+    }
+
+    /// 栈底（最低地址，其下为保护页）
+    fn bottom(&self) -> VirtualAddress {
+        KERNEL_AREA_START + self.slot * KERNEL_STACK_SLOT_SIZE + PAGE_SIZE
+    }
+
+    /// 栈顶
+    ///
+    /// 其上保留 16 字节（保持栈指针对齐），用于存放正在执行该线程的 hart 编号（见 `interrupt.asm`）
+    pub fn top(&self) -> usize {
+        self.bottom().0 + KERNEL_STACK_SIZE - 16
+    }
+
+    /// 在栈顶放入 `Context`，其下放入返回至 `__restore` 的 [`SwitchContext`]
+    ///
+    /// 返回的栈指针可以直接交给 `__switch`，切换后线程将从 `context` 开始执行
+    pub fn push_context(&self, context: Context) -> usize {
+        extern "C" {
+            fn __restore();
+        }
         // Context 的位置
-        let push_address = (stack_top - size_of::<Context>()) as *mut Context;
+        let context_address = self.top() - size_of::<Context>();
+        // SwitchContext 的位置
+        let switch_address = context_address - size_of::<SwitchContext>();
         unsafe {
-            *push_address = context;
+            *(context_address as *mut Context) = context;
+            *(switch_address as *mut SwitchContext) = SwitchContext {
+                ra: __restore as usize,
+                ..Default::default()
+            };
+        }
+        switch_address
+    }
+
+    /// 栈顶 `Context` 的位置
+    ///
+    /// 只有在线程位于内核中（或尚未开始执行）时，栈顶才是线程的 `Context`
+    pub fn context(&self) -> *mut Context {
+        (self.top() - size_of::<Context>()) as *mut Context
+    }
+}
+
+/// 释放时清除映射并回收栈的位置，物理页随 `frames` 一同释放
+impl Drop for KernelStack {
+    fn drop(&mut self) {
+        let start = VirtualPageNumber::floor(self.bottom());
+        let mut area = KERNEL_AREA.lock();
+        for i in 0..self.frames.len() {
+            area.unmap(start + i);
         }
-        push_address
+        KERNEL_STACK_SLOTS.lock().dealloc(self.slot);
     }
 }
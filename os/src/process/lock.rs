@@ -31,6 +31,23 @@ impl<T> Lock<T> {
             sstatus,
         }
     }
+
+    /// 关闭中断之后再通过 `select` 选出要上锁的对象
+    ///
+    /// 例如按照当前 hart 选择时，先关闭中断才能保证选择之后线程不会被切换到其他 hart 上
+    pub fn lock_selected<'a>(select: impl FnOnce() -> &'a Self) -> LockGuard<'a, T>
+    where
+        T: 'a,
+    {
+        let sstatus: usize;
+        unsafe {
+            llvm_asm!("csrrci $0, sstatus, 1 << 1" : "=r"(sstatus) ::: "volatile");
+        }
+        LockGuard {
+            guard: Some(select().0.lock()),
+            sstatus,
+        }
+    }
 }
 
 /// 释放时，先释放内部的 MutexGuard，再恢复 sstatus 寄存器
@@ -14,8 +14,11 @@ use alloc::{sync::Arc, vec, vec::Vec};
 use spin::Mutex;
 
 pub use config::*;
-pub use kernel_stack::KERNEL_STACK;
+pub use kernel_stack::{KernelStack, SwitchContext};
 pub use lock::Lock;
-pub use process::Process;
-pub use processor::PROCESSOR;
+pub use process::{FileMapping, Process, ProcessID, MAX_MAP_SIZE};
+pub use processor::{
+    exit_current_thread, preempt_current_thread, run_scheduler, switch_in_kernel_thread,
+    switch_to_scheduler, thread_count, PROCESSOR,
+};
 pub use thread::Thread;
@@ -2,21 +2,156 @@
 
 use super::*;
 use crate::fs::*;
+use crate::kernel::Condvar;
+use alloc::{collections::BTreeMap, string::String, sync::Weak};
+use core::cmp::min;
+use core::sync::atomic::{spin_loop_hint, AtomicIsize, Ordering};
 use xmas_elf::ElfFile;
 
+/// 进程 ID 使用 `isize`，可以用负数表示错误
+pub type ProcessID = isize;
+
+/// 进程计数，用于设置进程 ID（可能在多个 hart 上同时创建进程）
+static PROCESS_COUNTER: AtomicIsize = AtomicIsize::new(0);
+
+/// 动态分配的虚拟空间从这里开始查找
+const MAP_START_ADDRESS: usize = 0x100_0000;
+
+/// 用户部分虚拟空间的上界（Sv39 的低半部分）
+const MAP_END_ADDRESS: usize = 0x40_0000_0000;
+
+/// 一次动态分配的虚拟空间的长度上限，不超过物理内存和交换区一共能够容纳的页面
+pub const MAX_MAP_SIZE: usize =
+    MEMORY_END_ADDRESS.0 - MEMORY_START_ADDRESS.0 + SWAP_PAGE_COUNT * PAGE_SIZE;
+
 /// 进程的信息
 pub struct Process {
+    /// 进程 ID
+    pub pid: ProcessID,
     /// 是否属于用户态
     pub is_user: bool,
     /// 用 `Mutex` 包装一些可变的变量
     pub inner: Mutex<ProcessInner>,
+    /// 等待子进程退出的线程在此休眠
+    pub child_exited: Condvar,
 }
 
 pub struct ProcessInner {
     /// 进程中的线程公用页表 / 内存映射
     pub memory_set: MemorySet,
-    /// 打开的文件描述符
-    pub descriptors: Vec<Arc<dyn INode>>,
+    /// 打开的文件描述符，下标即为描述符编号，关闭的位置为 `None`
+    pub descriptors: Vec<Option<Arc<FileHandle>>>,
+    /// 当前工作目录，相对路径从这里开始查找
+    pub cwd: Arc<dyn INode>,
+    /// 当前工作目录的绝对路径（已化简，不含 `.` 和 `..`）
+    pub cwd_path: String,
+    /// 父进程
+    pub parent: Weak<Process>,
+    /// 子进程，包括已经退出但还未被回收的
+    pub children: Vec<Arc<Process>>,
+    /// 退出码，进程退出后（成为僵尸进程）为 `Some`
+    pub exit_code: Option<isize>,
+    /// 共享的文件映射，以映射的起始地址为键
+    pub file_mappings: BTreeMap<VirtualAddress, FileMapping>,
+    /// 进程中的线程，exec 时用来结束其他线程
+    threads: Vec<Weak<Thread>>,
+}
+
+/// 共享的文件映射（`MAP_SHARED`），解除映射、exec 或进程退出时将内容写回文件
+#[derive(Clone)]
+pub struct FileMapping {
+    /// 映射的文件
+    pub inode: Arc<dyn INode>,
+    /// 映射开头在文件中的位置
+    pub offset: usize,
+    /// 映射的长度
+    pub size: usize,
+}
+
+impl FileMapping {
+    /// 将映射的内容写回文件，不会超出文件原有的长度
+    fn write_back(&self, data: &[u8]) {
+        let file_size = self.inode.metadata().map_or(0, |metadata| metadata.size);
+        let size = min(data.len(), file_size.saturating_sub(self.offset));
+        // 此时映射已经解除，无法再通知进程，写入失败时直接忽略
+        let _ = self.inode.write_at(self.offset, &data[..size]);
+    }
+}
+
+impl ProcessInner {
+    /// 记录进程中新建的线程，同时清理已经释放的线程
+    pub fn add_thread(&mut self, thread: &Arc<Thread>) {
+        self.threads.retain(|thread| thread.strong_count() > 0);
+        self.threads.push(Arc::downgrade(thread));
+    }
+
+    /// 将文件放入编号最小的空闲描述符，返回描述符编号
+    ///
+    /// 已经有 [`MAX_DESCRIPTORS`] 个描述符时返回 `Err`
+    pub fn add_descriptor(&mut self, file: Arc<FileHandle>) -> Result<usize, &'static str> {
+        match self.descriptors.iter().position(Option::is_none) {
+            Some(fd) => {
+                self.descriptors[fd] = Some(file);
+                Ok(fd)
+            }
+            None if self.descriptors.len() < MAX_DESCRIPTORS => {
+                self.descriptors.push(Some(file));
+                Ok(self.descriptors.len() - 1)
+            }
+            None => Err("too many open files"),
+        }
+    }
+
+    /// 将文件放入描述符 `fd`，返回原先在这里的文件
+    ///
+    /// `fd` 超出现有的描述符表时会扩展描述符表，超过 [`MAX_DESCRIPTORS`] 时返回 `Err`
+    pub fn set_descriptor(
+        &mut self,
+        fd: usize,
+        file: Arc<FileHandle>,
+    ) -> Result<Option<Arc<FileHandle>>, &'static str> {
+        if fd >= MAX_DESCRIPTORS {
+            return Err("file descriptor out of range");
+        }
+        if fd >= self.descriptors.len() {
+            self.descriptors.resize(fd + 1, None);
+        }
+        Ok(self.descriptors[fd].replace(file))
+    }
+
+    /// 关闭描述符，描述符不存在时返回 `None`
+    pub fn remove_descriptor(&mut self, fd: usize) -> Option<Arc<FileHandle>> {
+        self.descriptors.get_mut(fd)?.take()
+    }
+
+    /// 移除起始于 `start` 的共享文件映射记录（`None` 表示全部），并读出需要写回文件的内容
+    ///
+    /// 写回文件在释放进程的锁之后进行（见 [`write_back`]）
+    fn take_file_mappings(&mut self, start: Option<VirtualAddress>) -> Vec<(FileMapping, Vec<u8>)> {
+        let starts: Vec<VirtualAddress> = self
+            .file_mappings
+            .keys()
+            .copied()
+            .filter(|&key| start.map_or(true, |start| start == key))
+            .collect();
+        let mut taken = Vec::new();
+        for key in starts {
+            let file_mapping = self.file_mappings.remove(&key).unwrap();
+            let mut data = vec![0; file_mapping.size];
+            // 共享映射的页面都已分配且不会被换出，可以直接从物理页读取
+            if self.memory_set.mapping.read_bytes(key, &mut data).is_ok() {
+                taken.push((file_mapping, data));
+            }
+        }
+        taken
+    }
+}
+
+/// 将 [`ProcessInner::take_file_mappings`] 取出的内容写回文件
+fn write_back(taken: Vec<(FileMapping, Vec<u8>)>) {
+    for (file_mapping, data) in taken {
+        file_mapping.write_back(&data);
+    }
 }
 
 #[allow(unused)]
@@ -24,25 +159,164 @@ impl Process {
     /// 创建一个内核进程
     pub fn new_kernel() -> MemoryResult<Arc<Self>> {
         Ok(Arc::new(Self {
+            pid: Self::new_pid(),
             is_user: false,
             inner: Mutex::new(ProcessInner {
                 memory_set: MemorySet::new_kernel()?,
-                descriptors: vec![STDIN.clone(), STDOUT.clone()],
+                descriptors: default_descriptors(),
+                cwd: ROOT_INODE.clone(),
+                cwd_path: String::from("/"),
+                parent: Weak::new(),
+                children: Vec::new(),
+                exit_code: None,
+                file_mappings: BTreeMap::new(),
+                threads: Vec::new(),
             }),
+            child_exited: Condvar::default(),
         }))
     }
 
     /// 创建进程，从文件中读取代码
     pub fn from_elf(file: &ElfFile, is_user: bool) -> MemoryResult<Arc<Self>> {
         Ok(Arc::new(Self {
+            pid: Self::new_pid(),
             is_user,
             inner: Mutex::new(ProcessInner {
                 memory_set: MemorySet::from_elf(file, is_user)?,
-                descriptors: vec![STDIN.clone(), STDOUT.clone()],
+                descriptors: default_descriptors(),
+                cwd: ROOT_INODE.clone(),
+                cwd_path: String::from("/"),
+                parent: Weak::new(),
+                children: Vec::new(),
+                exit_code: None,
+                file_mappings: BTreeMap::new(),
+                threads: Vec::new(),
             }),
+            child_exited: Condvar::default(),
         }))
     }
 
+    /// 复制一个进程（用于 fork）
+    ///
+    /// 以写时复制的方式共享其内存映射中所有按帧分配的字段，并复制打开的文件描述符和工作目录。
+    /// 新进程会记为当前进程的子进程
+    pub fn fork(self: &Arc<Self>) -> MemoryResult<Arc<Self>> {
+        let mut inner = self.inner();
+        let child = Arc::new(Self {
+            pid: Self::new_pid(),
+            is_user: self.is_user,
+            inner: Mutex::new(ProcessInner {
+                memory_set: inner.memory_set.fork()?,
+                descriptors: inner.descriptors.clone(),
+                cwd: inner.cwd.clone(),
+                cwd_path: inner.cwd_path.clone(),
+                parent: Arc::downgrade(self),
+                children: Vec::new(),
+                exit_code: None,
+                // 共享映射的物理页与子进程共享，子进程同样需要写回
+                file_mappings: inner.file_mappings.clone(),
+                threads: Vec::new(),
+            }),
+            child_exited: Condvar::default(),
+        });
+        inner.children.push(child.clone());
+        Ok(child)
+    }
+
+    /// 用 ELF 文件中的程序替换进程的内存映射（用于 exec），`current` 为调用 exec 的线程
+    ///
+    /// 进程中的其他线程都会被结束，原有的内存映射会被释放，共享的文件映射会被写回。
+    /// 新映射中不包含栈，需要另行分配。以 [`OpenFlags::CLOEXEC`] 打开的文件会被关闭
+    pub fn exec(&self, file: &ElfFile, current: &Arc<Thread>) -> MemoryResult<()> {
+        // 先建立新的映射，失败时进程保持原样
+        let memory_set = MemorySet::from_elf(file, self.is_user)?;
+        self.kill_other_threads(current)?;
+        let mut inner = self.inner();
+        let file_mappings = inner.take_file_mappings(None);
+        let old_memory_set = core::mem::replace(&mut inner.memory_set, memory_set);
+        // 当前正在使用旧的页表，必须先切换到新的页表，再释放旧的映射
+        inner.memory_set.activate();
+        drop(old_memory_set);
+        // 关闭标记为 close-on-exec 的文件
+        for descriptor in inner.descriptors.iter_mut() {
+            if let Some(file) = descriptor {
+                if file.flags.contains(OpenFlags::CLOEXEC) {
+                    *descriptor = None;
+                }
+            }
+        }
+        drop(inner);
+        write_back(file_mappings);
+        Ok(())
+    }
+
+    /// 结束进程中除 `current` 以外的所有线程，并等待它们不再执行
+    ///
+    /// 其他线程被标记为结束：正在执行的线程在下一次进入中断时结束，这里等待它切换出去；
+    /// 其余线程不会再被调度（见 [`Thread::prepare`]）。此后不会有其他线程访问进程的内存映射。
+    /// 两个线程同时调用时，后持有锁的一方已被结束，返回 `Err`
+    fn kill_other_threads(&self, current: &Arc<Thread>) -> MemoryResult<()> {
+        let running: Vec<Arc<Thread>> = {
+            let mut inner = self.inner();
+            if current.inner().dead {
+                return Err("thread is killed");
+            }
+            let mut running = Vec::new();
+            for thread in inner.threads.iter().filter_map(Weak::upgrade) {
+                if thread.id == current.id {
+                    continue;
+                }
+                let mut thread_inner = thread.inner();
+                thread_inner.dead = true;
+                if thread_inner.running {
+                    drop(thread_inner);
+                    running.push(thread);
+                }
+            }
+            inner.threads = vec![Arc::downgrade(current)];
+            running
+        };
+        // 等待时不能持有进程的锁，正在执行的线程可能需要它
+        for thread in running {
+            while thread.inner().running {
+                spin_loop_hint();
+            }
+        }
+        Ok(())
+    }
+
+    /// 结束进程并记录退出码，此后进程成为僵尸进程，直到被父进程回收
+    ///
+    /// 进程的内存和文件描述符会立即释放，子进程不再有父进程，等待中的父进程会被唤醒。
+    /// 进程中其余的线程会在下一次进入中断时被终止
+    pub fn exit(&self, code: isize) {
+        let mut inner = self.inner();
+        if inner.exit_code.is_some() {
+            return;
+        }
+        inner.exit_code = Some(code);
+        inner.descriptors.clear();
+        let file_mappings = inner.take_file_mappings(None);
+        // 只保留内核映射，当前线程在退出前仍在使用
+        inner.memory_set.clear();
+        for child in inner.children.drain(..) {
+            child.inner().parent = Weak::new();
+        }
+        let parent = inner.parent.upgrade();
+        drop(inner);
+        write_back(file_mappings);
+        if let Some(parent) = parent {
+            // 持有父进程的锁来唤醒，使得父进程的检查和休眠不会与此交错
+            let _parent_inner = parent.inner();
+            parent.child_exited.notify_all();
+        }
+    }
+
+    /// 进程是否已经退出
+    pub fn is_exited(&self) -> bool {
+        self.inner().exit_code.is_some()
+    }
+
     /// 上锁并获得可变部分的引用
     pub fn inner(&self) -> spin::MutexGuard<ProcessInner> {
         self.inner.lock()
@@ -50,7 +324,8 @@ impl Process {
 
     /// 分配一定数量的连续虚拟空间
     ///
-    /// 从 `memory_set` 中找到一段给定长度的未占用虚拟地址空间，分配物理页面并建立映射。返回对应的页面区间。
+    /// 从 `memory_set` 中找到一段给定长度的未占用虚拟地址空间，建立按需分配的映射。返回对应的页面区间。
+    /// 物理页面会在第一次访问时才分配。
     ///
     /// `flags` 只需包括 rwx 权限，user 位会根据进程而定。
     pub fn alloc_page_range(
@@ -58,26 +333,143 @@ impl Process {
         size: usize,
         flags: Flags,
     ) -> MemoryResult<Range<VirtualAddress>> {
+        self.map_range(size, MapType::Lazy, flags, None)
+    }
+
+    /// 分配一段连续虚拟空间并建立 `map_type` 类型的映射（用于线程栈和 mmap）
+    ///
+    /// `data` 为映射开头部分的初始数据，其余部分填充为零。返回对应的地址区间。
+    ///
+    /// `flags` 只需包括 rwx 权限，user 位会根据进程而定。
+    pub fn map_range(
+        &self,
+        size: usize,
+        map_type: MapType,
+        flags: Flags,
+        data: Option<&[u8]>,
+    ) -> MemoryResult<Range<VirtualAddress>> {
+        if size > MAX_MAP_SIZE {
+            return Err("mapping is too large");
+        }
         let memory_set = &mut self.inner().memory_set;
 
         // memory_set 只能按页分配，所以让 size 向上取整页
         let alloc_size = (size + PAGE_SIZE - 1) & !(PAGE_SIZE - 1);
         // 从 memory_set 中找一段不会发生重叠的空间
-        let mut range = Range::<VirtualAddress>::from(0x1000000..0x1000000 + alloc_size);
+        let mut range =
+            Range::<VirtualAddress>::from(MAP_START_ADDRESS..MAP_START_ADDRESS + alloc_size);
         while memory_set.overlap_with(range.into()) {
+            if range.end.0 > MAP_END_ADDRESS - alloc_size {
+                return Err("no free virtual space");
+            }
             range.start += alloc_size;
             range.end += alloc_size;
         }
-        // 分配物理页面，建立映射
+        // 记录映射
         memory_set.add_segment(
             Segment {
-                map_type: MapType::Framed,
+                map_type,
                 range,
                 flags: flags | Flags::user(self.is_user),
             },
-            None,
+            data,
         )?;
         // 返回地址区间（使用参数 size，而非向上取整的 alloc_size）
         Ok(Range::from(range.start..(range.start + size)))
     }
+
+    /// 分配一段连续虚拟空间，映射文件 `inode` 中从 `offset` 开始的 `length` 字节（用于 mmap）
+    ///
+    /// `map_type` 必须立即分配物理页。文件的内容逐页读入映射的物理页，而不在内核堆中缓存，
+    /// 其余部分填充为零。读取文件时不持有进程的锁，读取失败时解除映射
+    pub fn map_file(
+        &self,
+        size: usize,
+        map_type: MapType,
+        flags: Flags,
+        inode: &Arc<dyn INode>,
+        offset: usize,
+        length: usize,
+    ) -> MemoryResult<Range<VirtualAddress>> {
+        assert!(map_type == MapType::Framed || map_type == MapType::Shared);
+        let range = self.map_range(size, map_type, flags, None)?;
+        let mut page = [0u8; PAGE_SIZE];
+        let mut copied = 0;
+        while copied < length {
+            let page_size = min(PAGE_SIZE, length - copied);
+            let read = match inode.read_at(offset + copied, &mut page[..page_size]) {
+                Ok(read) => read,
+                Err(_) => {
+                    let _ = self.unmap_range(range.start, size);
+                    return Err("failed to read file");
+                }
+            };
+            let mut inner = self.inner();
+            // 读取期间映射可能已被同一进程的其他线程解除，此时不再写入
+            inner
+                .memory_set
+                .find_segment(range.start, size)
+                .ok_or("mapping is removed while reading file")?;
+            let written = inner
+                .memory_set
+                .mapping
+                .write_bytes(range.start + copied, &page[..read]);
+            drop(inner);
+            if let Err(msg) = written {
+                let _ = self.unmap_range(range.start, size);
+                return Err(msg);
+            }
+            // 文件在读取期间变短，剩余部分保持为零
+            if read < page_size {
+                break;
+            }
+            copied += read;
+        }
+        Ok(range)
+    }
+
+    /// 解除恰好为 `[start, start + size)` 的映射（按页取整，用于 munmap）
+    ///
+    /// 共享的文件映射会先写回文件
+    pub fn unmap_range(&self, start: VirtualAddress, size: usize) -> MemoryResult<()> {
+        let mut inner = self.inner();
+        let segment = inner
+            .memory_set
+            .find_segment(start, size)
+            .ok_or("range is not a mapped segment")?;
+        let file_mappings = inner.take_file_mappings(Some(start));
+        inner.memory_set.remove_segment(&segment)?;
+        drop(inner);
+        write_back(file_mappings);
+        Ok(())
+    }
+
+    /// 将恰好为 `[start, start + size)` 的映射的权限改为 `flags`（按页取整，用于 mprotect）
+    ///
+    /// `flags` 只需包括 rwx 权限，user 位会根据进程而定。
+    pub fn protect_range(
+        &self,
+        start: VirtualAddress,
+        size: usize,
+        flags: Flags,
+    ) -> MemoryResult<()> {
+        let memory_set = &mut self.inner().memory_set;
+        memory_set
+            .find_segment(start, size)
+            .ok_or("range is not a mapped segment")?;
+        memory_set.protect_segment(start, flags | Flags::user(self.is_user))
+    }
+
+    /// 分配一个新的进程 ID
+    fn new_pid() -> ProcessID {
+        PROCESS_COUNTER.fetch_add(1, Ordering::Relaxed) + 1
+    }
+}
+
+/// 新进程默认打开的文件：0 为标准输入，1 为标准输出
+fn default_descriptors() -> Vec<Option<Arc<FileHandle>>> {
+    vec![
+        Some(FileHandle::new(STDIN.clone(), OpenFlags::READ)),
+        Some(FileHandle::new(STDOUT.clone(), OpenFlags::WRITE)),
+    ]
 }
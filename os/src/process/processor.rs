@@ -1,54 +1,71 @@
 //! 实现线程的调度和管理 [`Processor`]
 
+use super::lock::LockGuard;
 use super::*;
+use crate::hart::{self, hart_id, MAX_HART_COUNT};
+use crate::memory::mapping::Mapping;
 use algorithm::*;
+use core::sync::atomic::{AtomicUsize, Ordering};
 use hashbrown::HashSet;
 use lazy_static::*;
 
 lazy_static! {
-    /// 全局的 [`Processor`]
-    pub static ref PROCESSOR: Lock<Processor> = Lock::new(Processor::default());
+    /// 每个 hart 各自的 [`Processor`]
+    static ref PROCESSORS: Vec<Lock<Processor>> =
+        (0..MAX_HART_COUNT).map(|_| Lock::new(Processor::default())).collect();
+    /// 所有 hart 共享的休眠线程，被唤醒后加入唤醒者所在 hart 的调度器
+    static ref SLEEPING_THREADS: Lock<HashSet<Arc<Thread>>> = Lock::new(HashSet::new());
 }
 
-lazy_static! {
-    /// 空闲线程：当所有线程进入休眠时，切换到这个线程——它什么都不做，只会等待下一次中断
-    static ref IDLE_THREAD: Arc<Thread> = Thread::new(
-        Process::new_kernel().unwrap(),
-        wait_for_interrupt as usize,
-        None,
-    ).unwrap();
+/// 当前 hart 的 [`Processor`]，通过 `PROCESSOR.lock()` 访问
+pub static PROCESSOR: CurrentProcessor = CurrentProcessor;
+
+/// 尚未结束的线程数量（包括休眠的线程），降为 0 时关机
+static THREAD_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+global_asm!(include_str!("./switch.asm"));
+
+extern "C" {
+    /// 保存当前的寄存器和栈指针至 `current_sp`，然后切换到 `next_sp` 所指的栈（见 `switch.asm`）
+    fn __switch(current_sp: *mut usize, next_sp: usize);
 }
 
-/// 不断让 CPU 进入休眠等待下一次中断
-unsafe fn wait_for_interrupt() {
-    loop {
-        llvm_asm!("wfi" :::: "volatile");
+/// 用于访问当前 hart 的 [`Processor`]
+pub struct CurrentProcessor;
+
+impl CurrentProcessor {
+    /// 获得当前 hart 的 [`Processor`] 并上锁
+    pub fn lock(&self) -> LockGuard<'static, Processor> {
+        Lock::lock_selected(|| &PROCESSORS[hart_id()])
     }
 }
 
-/// 线程调度和管理
+/// 一个 hart 上的线程调度和管理
+///
+/// 每个 hart 有自己的调度器，新建或被唤醒的线程加入当前 hart 的调度器。
+/// 某个 hart 没有活跃线程时，会从其他 hart 的调度器中窃取一个。
 ///
 /// 休眠线程会从调度器中移除，单独保存。在它们被唤醒之前，不会被调度器安排。
 ///
+/// 调度循环 [`run_scheduler`] 运行在各个 hart 的启动栈上，线程通过 [`switch_to_scheduler`] 回到调度循环，
+/// 之后被再次调度时（可能在另一个 hart 上）从切换的位置继续执行。
+///
 /// # 用例
 ///
-/// ### 切换线程（在中断中）
+/// ### 切换线程（在时钟中断中）
 /// ```rust
-/// processor.park_current_thread(context);
-/// processor.prepare_next_thread()
+/// preempt_current_thread();
 /// ```
 ///
 /// ### 结束线程（在中断中）
 /// ```rust
-/// processor.kill_current_thread();
-/// processor.prepare_next_thread()
+/// exit_current_thread();
 /// ```
 ///
 /// ### 休眠线程（在中断中）
 /// ```rust
-/// processor.park_current_thread(context);
-/// processor.sleep_current_thread();
-/// processor.prepare_next_thread()
+/// PROCESSOR.lock().sleep_current_thread();
+/// switch_to_scheduler();
 /// ```
 ///
 /// ### 唤醒线程
@@ -60,10 +77,19 @@ unsafe fn wait_for_interrupt() {
 pub struct Processor {
     /// 当前正在执行的线程
     current_thread: Option<Arc<Thread>>,
-    /// 线程调度器，记录活跃线程
+    /// 线程调度器，记录这个 hart 上的活跃线程
     scheduler: SchedulerImpl<Arc<Thread>>,
-    /// 保存休眠线程
-    sleeping_threads: HashSet<Arc<Thread>>,
+    /// 调度器中的所有线程，其他 hart 空闲时从中窃取
+    threads: HashSet<Arc<Thread>>,
+    /// 调度循环切换到线程时保存的栈指针
+    scheduler_sp: usize,
+    /// 当前线程是否被时钟中断抢占（而不是休眠或结束）
+    preempted: bool,
+    /// 这个 hart 当前加载的页表所属的进程
+    ///
+    /// 线程切换回调度循环之后不会换回启动页表，下一个线程属于同一进程时也就不需要重新激活。
+    /// 持有进程以免页表在仍被加载时被释放
+    loaded_process: Option<Arc<Process>>,
 }
 
 impl Processor {
@@ -72,59 +98,226 @@ impl Processor {
         self.current_thread.as_ref().unwrap().clone()
     }
 
-    /// 激活下一个线程的 `Context`
-    pub fn prepare_next_thread(&mut self) -> *mut Context {
-        // 向调度器询问下一个线程
-        if let Some(next_thread) = self.scheduler.get_next() {
-            // 准备下一个线程
-            let context = next_thread.prepare();
-            self.current_thread = Some(next_thread);
-            context
-        } else {
-            // 没有活跃线程
-            if self.sleeping_threads.is_empty() {
-                // 也没有休眠线程，则退出
-                panic!("all threads terminated, shutting down");
+    /// 选出下一个线程并激活其页表，返回切换到它所用的栈指针，以及之前加载的页表所属的进程
+    ///
+    /// 同时告知调度器上一个线程是否被抢占。没有活跃线程时返回 `None`
+    fn prepare_next_thread(&mut self) -> Option<(usize, Option<Arc<Process>>)> {
+        let mut preempted = core::mem::replace(&mut self.preempted, false);
+        // 向调度器询问下一个线程，所属进程已经退出或者已被结束的线程直接移除
+        while let Some(next_thread) = self.scheduler.get_next(preempted) {
+            // 准备下一个线程，此后不再使用之前加载的页表
+            let kernel_sp = if next_thread.process.is_exited() {
+                None
             } else {
-                // 有休眠线程，则等待中断
-                self.current_thread = Some(IDLE_THREAD.clone());
-                IDLE_THREAD.prepare()
-            }
+                next_thread.prepare()
+            };
+            let kernel_sp = match kernel_sp {
+                Some(kernel_sp) => kernel_sp,
+                None => {
+                    self.unschedule(&next_thread);
+                    THREAD_COUNT.fetch_sub(1, Ordering::AcqRel);
+                    preempted = false;
+                    continue;
+                }
+            };
+            let previous = self.loaded_process.replace(next_thread.process.clone());
+            self.current_thread = Some(next_thread);
+            return Some((kernel_sp, previous));
         }
+        None
     }
 
     /// 添加一个待执行的线程
     pub fn add_thread(&mut self, thread: Arc<Thread>) {
-        self.scheduler.add_thread(thread);
+        THREAD_COUNT.fetch_add(1, Ordering::AcqRel);
+        self.schedule(thread);
+        hart::wake_idle_harts();
     }
 
     /// 唤醒一个休眠线程
     pub fn wake_thread(&mut self, thread: Arc<Thread>) {
         thread.inner().sleeping = false;
-        self.sleeping_threads.remove(&thread);
-        self.scheduler.add_thread(thread);
+        SLEEPING_THREADS.lock().remove(&thread);
+        self.schedule(thread);
+        hart::wake_idle_harts();
     }
 
-    /// 保存当前线程的 `Context`
-    pub fn park_current_thread(&mut self, context: &Context) {
-        self.current_thread().park(*context);
+    /// 设置当前线程的优先级
+    ///
+    /// 优先级保存在线程中，线程休眠后重新加入调度器时仍然有效
+    pub fn set_current_priority(&mut self, priority: usize) {
+        let thread = self.current_thread();
+        thread.inner().priority = priority;
+        self.scheduler.set_priority(thread, priority);
     }
 
-    /// 令当前线程进入休眠
+    /// 令当前线程进入休眠，之后应当调用 [`switch_to_scheduler`]
     pub fn sleep_current_thread(&mut self) {
         // 从 current_thread 中取出
         let current_thread = self.current_thread();
         // 记为 sleeping
         current_thread.inner().sleeping = true;
         // 从 scheduler 移出到 sleeping_threads 中
-        self.scheduler.remove_thread(&current_thread);
-        self.sleeping_threads.insert(current_thread);
+        self.unschedule(&current_thread);
+        SLEEPING_THREADS.lock().insert(current_thread);
     }
 
-    /// 终止当前的线程
+    /// 终止当前的线程，之后应当调用 [`switch_to_scheduler`]，且不会再返回
+    ///
+    /// 线程仍然保留在 `current_thread` 中，直到调度循环切换回来后才释放，因为此时仍在使用它的内核栈
     pub fn kill_current_thread(&mut self) {
+        let thread = self.current_thread();
+        thread.inner().dead = true;
         // 从调度器中移除
-        let thread = self.current_thread.take().unwrap();
-        self.scheduler.remove_thread(&thread);
+        self.unschedule(&thread);
+        THREAD_COUNT.fetch_sub(1, Ordering::AcqRel);
+    }
+
+    /// 将线程加入这个 hart 的调度器
+    fn schedule(&mut self, thread: Arc<Thread>) {
+        let priority = thread.inner().priority;
+        self.scheduler.add_thread(thread.clone());
+        self.scheduler.set_priority(thread.clone(), priority);
+        self.threads.insert(thread);
+    }
+
+    /// 将线程移出这个 hart 的调度器
+    fn unschedule(&mut self, thread: &Arc<Thread>) {
+        self.scheduler.remove_thread(thread);
+        self.threads.remove(thread);
+    }
+
+    /// 移出一个没有在执行的线程，交给其他空闲的 hart
+    fn steal_thread(&mut self) -> Option<Arc<Thread>> {
+        let current_thread = self.current_thread.as_ref();
+        let thread = self
+            .threads
+            .iter()
+            .find(|thread| Some(*thread) != current_thread)?
+            .clone();
+        self.unschedule(&thread);
+        Some(thread)
+    }
+}
+
+/// 调度循环，不断选出线程并切换过去执行，不会返回
+///
+/// 运行在当前 hart 的启动栈上。没有活跃线程时先尝试从其他 hart 窃取，仍然没有则等待中断；
+/// 所有线程都结束后关机
+pub fn run_scheduler() -> ! {
+    hart::set_running();
+    loop {
+        let switch = {
+            let mut processor = PROCESSOR.lock();
+            processor
+                .prepare_next_thread()
+                .map(|(kernel_sp, previous)| {
+                    (
+                        &mut processor.scheduler_sp as *mut usize,
+                        kernel_sp,
+                        previous,
+                    )
+                })
+        };
+        match switch {
+            Some((scheduler_sp, kernel_sp, previous)) => {
+                // 释放进程时可能需要访问 Processor，因此在锁外释放
+                drop(previous);
+                unsafe { __switch(scheduler_sp, kernel_sp) };
+                // 线程切换回来，页表仍然保留，由 loaded_process 保证它不会被释放
+                let thread = PROCESSOR.lock().current_thread.take().unwrap();
+                // 现场已经保存好，线程可以在其他 hart 上继续执行了
+                thread.inner().running = false;
+                drop(thread);
+            }
+            None if steal_thread() => {}
+            None if THREAD_COUNT.load(Ordering::Acquire) == 0 => {
+                // 也没有休眠线程，则退出
+                panic!("all threads terminated, shutting down")
+            }
+            // 有休眠线程，或者线程都在其他 hart 上执行，则等待中断
+            None => {
+                unload_process();
+                wait_for_interrupt();
+            }
+        }
+    }
+}
+
+/// 从其他 hart 的调度器中窃取一个线程，加入当前 hart 的调度器
+fn steal_thread() -> bool {
+    let id = hart_id();
+    for other in (0..MAX_HART_COUNT).filter(|&other| other != id) {
+        let thread = PROCESSORS[other].lock().steal_thread();
+        if let Some(thread) = thread {
+            PROCESSOR.lock().schedule(thread);
+            return true;
+        }
+    }
+    false
+}
+
+/// 换回启动页表，不再持有之前加载的页表所属的进程，以便空闲期间它可以被释放
+fn unload_process() {
+    Mapping::activate_boot();
+    let process = PROCESSOR.lock().loaded_process.take();
+    drop(process);
+}
+
+/// 尚未结束的线程数量，包括休眠的线程
+pub fn thread_count() -> usize {
+    THREAD_COUNT.load(Ordering::Acquire)
+}
+
+/// 从当前线程切换到调度循环，线程被再次调度时从这里返回
+///
+/// 只能在中断处理流程中调用，且调用时不能持有任何锁
+pub fn switch_to_scheduler() {
+    let (kernel_sp, scheduler_sp) = {
+        let processor = PROCESSOR.lock();
+        // 线程由 current_thread 持有，所以这个指针在切换期间一直有效
+        let kernel_sp = &mut processor.current_thread().inner().kernel_sp as *mut usize;
+        (kernel_sp, processor.scheduler_sp)
+    };
+    unsafe { __switch(kernel_sp, scheduler_sp) };
+}
+
+/// 在内核线程中执行需要切换线程的 `f`，例如在条件变量上休眠
+///
+/// [`switch_to_scheduler`] 只能在关闭中断的中断处理流程中调用，而内核线程执行时开启中断。
+/// 这里在 `f` 执行期间关闭中断，线程被再次调度、`f` 返回之后再恢复。调用时不能持有任何锁
+pub fn switch_in_kernel_thread<T>(f: impl FnOnce() -> T) -> T {
+    let sstatus: usize;
+    unsafe { llvm_asm!("csrrci $0, sstatus, 1 << 1" : "=r"(sstatus) ::: "volatile") };
+    let result = f();
+    unsafe { llvm_asm!("csrs sstatus, $0" :: "r"(sstatus & 2) :: "volatile") };
+    result
+}
+
+/// 当前线程被时钟中断抢占，切换到调度循环，线程被再次调度时从这里返回
+///
+/// 只能在中断处理流程中调用，且调用时不能持有任何锁
+pub fn preempt_current_thread() {
+    PROCESSOR.lock().preempted = true;
+    switch_to_scheduler();
+}
+
+/// 结束当前线程，切换到调度循环，不再返回
+///
+/// 只能在中断处理流程中调用，且调用时不能持有任何锁
+pub fn exit_current_thread() -> ! {
+    PROCESSOR.lock().kill_current_thread();
+    switch_to_scheduler();
+    unreachable!()
+}
+
+/// 开启中断并让 CPU 休眠，等到一次中断处理完成后返回
+///
+/// 等待期间标记为空闲，其他 hart 有新的线程时会发送核间中断唤醒这里
+fn wait_for_interrupt() {
+    hart::set_idle(true);
+    unsafe {
+        llvm_asm!("csrsi sstatus, 1 << 1; wfi; csrci sstatus, 1 << 1" :::: "volatile");
     }
+    hart::set_idle(false);
 }
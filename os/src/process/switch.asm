@@ -0,0 +1,49 @@
+# 线程切换 __switch
+#
+# 将当前的被调用者保存寄存器存入当前栈，记录栈指针，然后切换到另一个栈并恢复其中的寄存器。
+# 调用者保存的寄存器已经由编译器在调用前保存好了，因此只需要保存 ra 和 s0 至 s11
+
+.altmacro
+# 寄存器宽度对应的字节数
+.set    REG_SIZE, 8
+# SwitchContext 的大小
+.set    SWITCH_CONTEXT_SIZE, 13
+
+# 宏：将 s 寄存器存到栈上，s_n 位于第 n + 1 个位置
+.macro SAVE_S n
+    sd s\n, (\n + 1) * REG_SIZE(sp)
+.endm
+
+# 宏：将 s 寄存器从栈中取出
+.macro LOAD_S n
+    ld s\n, (\n + 1) * REG_SIZE(sp)
+.endm
+
+    .section .text
+    .globl __switch
+# __switch(current_sp: *mut usize, next_sp: usize)
+# a0：保存当前栈指针的位置
+# a1：要切换到的栈指针，栈顶应当是一个 SwitchContext
+__switch:
+    # 在当前栈上开辟 SwitchContext 的空间并保存
+    addi    sp, sp, -SWITCH_CONTEXT_SIZE * REG_SIZE
+    sd      ra, 0(sp)
+    .set    n, 0
+    .rept   12
+        SAVE_S  %n
+        .set    n, n + 1
+    .endr
+    # 记录当前的栈指针
+    sd      sp, 0(a0)
+
+    # 切换到另一个栈并恢复
+    mv      sp, a1
+    ld      ra, 0(sp)
+    .set    n, 0
+    .rept   12
+        LOAD_S  %n
+        .set    n, n + 1
+    .endr
+    addi    sp, sp, SWITCH_CONTEXT_SIZE * REG_SIZE
+    # 返回到另一个栈所记录的位置
+    ret
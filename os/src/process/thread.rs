@@ -2,31 +2,36 @@
 
 use super::*;
 use core::hash::{Hash, Hasher};
+use core::sync::atomic::{spin_loop_hint, AtomicIsize, Ordering};
 
 /// 线程 ID 使用 `isize`，可以用负数表示错误
 pub type ThreadID = isize;
 
-/// 线程计数，用于设置线程 ID
-static mut THREAD_COUNTER: ThreadID = 0;
+/// 线程计数，用于设置线程 ID（可能在多个 hart 上同时创建线程）
+static THREAD_COUNTER: AtomicIsize = AtomicIsize::new(0);
 
 /// 线程的信息
 pub struct Thread {
     /// 线程 ID
     pub id: ThreadID,
-    /// 线程的栈
-    pub stack: Range<VirtualAddress>,
     /// 所属的进程
     pub process: Arc<Process>,
+    /// 线程的内核栈
+    pub kernel_stack: KernelStack,
     /// 用 `Mutex` 包装一些可变的变量
     pub inner: Mutex<ThreadInner>,
 }
 
 /// 线程中需要可变的部分
 pub struct ThreadInner {
-    /// 线程执行上下文
-    ///
-    /// 当且仅当线程被暂停执行时，`context` 为 `Some`
-    pub context: Option<Context>,
+    /// 线程切换出去时，内核栈上保存的栈指针（见 [`SwitchContext`]）
+    pub kernel_sp: usize,
+    /// 线程的栈（exec 之后会更换）
+    pub stack: Range<VirtualAddress>,
+    /// 调度优先级，越大则分到的时间片越多
+    pub priority: usize,
+    /// 是否正在某个 hart 上执行，直到切换出去的过程完成才清除
+    pub running: bool,
     /// 是否进入休眠
     pub sleeping: bool,
     /// 是否已经结束
@@ -36,22 +41,27 @@ pub struct ThreadInner {
 impl Thread {
     /// 准备执行一个线程
     ///
-    /// 激活对应进程的页表，并返回其 Context
-    pub fn prepare(&self) -> *mut Context {
+    /// 激活对应进程的页表（当前 hart 已经加载时跳过），并返回切换到线程所用的栈指针。
+    /// 线程已被结束（见 [`Process::exec`]）时返回 `None`，不再执行
+    ///
+    /// 线程刚刚在其他 hart 上休眠又被唤醒时，那个 hart 可能还没有完成切换，需要等待它保存好现场
+    pub fn prepare(&self) -> Option<usize> {
+        loop {
+            let mut inner = self.inner();
+            // 与标记结束在同一把锁下检查，被标记之后线程不会再开始执行
+            if inner.dead {
+                return None;
+            }
+            if !inner.running {
+                inner.running = true;
+                break;
+            }
+            drop(inner);
+            spin_loop_hint();
+        }
         // 激活页表
         self.process.inner().memory_set.activate();
-        // 取出 Context
-        let parked_frame = self.inner().context.take().unwrap();
-        // 将 Context 放至内核栈顶
-        unsafe { KERNEL_STACK.push_context(parked_frame) }
-    }
-
-    /// 发生时钟中断后暂停线程，保存状态
-    pub fn park(&self, context: Context) {
-        // 检查目前线程内的 context 应当为 None
-        assert!(self.inner().context.is_none());
-        // 将 Context 保存到线程中
-        self.inner().context.replace(context);
+        Some(self.inner().kernel_sp)
     }
 
     /// 创建一个线程
@@ -63,24 +73,54 @@ impl Thread {
         // 让所属进程分配并映射一段空间，作为线程的栈
         let stack = process.alloc_page_range(STACK_SIZE, Flags::READABLE | Flags::WRITABLE)?;
 
-        // 构建线程的 Context
+        // 构建线程的 Context，放在内核栈上
         let context = Context::new(stack.end.into(), entry_point, arguments, process.is_user);
+        let kernel_stack = KernelStack::new()?;
+        let kernel_sp = kernel_stack.push_context(context);
 
         // 打包成线程
         let thread = Arc::new(Thread {
-            id: unsafe {
-                THREAD_COUNTER += 1;
-                THREAD_COUNTER
-            },
-            stack,
+            id: THREAD_COUNTER.fetch_add(1, Ordering::Relaxed) + 1,
             process,
+            kernel_stack,
             inner: Mutex::new(ThreadInner {
-                context: Some(context),
+                kernel_sp,
+                stack,
+                priority: DEFAULT_PRIORITY,
+                running: false,
                 sleeping: false,
                 dead: false,
             }),
         });
+        thread.process.inner().add_thread(&thread);
+
+        Ok(thread)
+    }
 
+    /// 将线程复制到另一个进程中（用于 fork）
+    ///
+    /// 新线程使用相同区间的栈（其数据已随进程一同复制）和相同的优先级，并从给定的 `context` 继续执行
+    pub fn fork(&self, process: Arc<Process>, context: Context) -> MemoryResult<Arc<Thread>> {
+        let kernel_stack = KernelStack::new()?;
+        let kernel_sp = kernel_stack.push_context(context);
+        let (stack, priority) = {
+            let inner = self.inner();
+            (inner.stack, inner.priority)
+        };
+        let thread = Arc::new(Thread {
+            id: THREAD_COUNTER.fetch_add(1, Ordering::Relaxed) + 1,
+            process,
+            kernel_stack,
+            inner: Mutex::new(ThreadInner {
+                kernel_sp,
+                stack,
+                priority,
+                running: false,
+                sleeping: false,
+                dead: false,
+            }),
+        });
+        thread.process.inner().add_thread(&thread);
         Ok(thread)
     }
 
@@ -114,11 +154,13 @@ impl Hash for Thread {
 /// 打印线程除了父进程以外的信息
 impl core::fmt::Debug for Thread {
     fn fmt(&self, formatter: &mut core::fmt::Formatter) -> core::fmt::Result {
+        let inner = self.inner();
         formatter
             .debug_struct("Thread")
             .field("thread_id", &self.id)
-            .field("stack", &self.stack)
-            .field("context", &self.inner().context)
+            .field("stack", &inner.stack)
+            .field("priority", &inner.priority)
+            .field("kernel_stack_top", &self.kernel_stack.top())
             .finish()
     }
 }
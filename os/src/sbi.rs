@@ -16,6 +16,24 @@ fn sbi_call(which: usize, arg0: usize, arg1: usize, arg2: usize) -> usize {
     ret
 }
 
+/// SBI v0.2 扩展调用，扩展编号放在 `a7`，功能编号放在 `a6`
+///
+/// 返回错误码（0 表示成功），`a1` 中的返回值目前用不到
+#[inline(always)]
+fn sbi_call_ext(extension: usize, function: usize, arg0: usize, arg1: usize, arg2: usize) -> isize {
+    let mut error;
+    let mut _value: usize;
+    unsafe {
+        llvm_asm!("ecall"
+            : "={x10}" (error), "={x11}" (_value)
+            : "{x10}" (arg0), "{x11}" (arg1), "{x12}" (arg2),
+              "{x16}" (function), "{x17}" (extension)
+            : "memory"
+            : "volatile");
+    }
+    error
+}
+
 const SBI_SET_TIMER: usize = 0;
 const SBI_CONSOLE_PUTCHAR: usize = 1;
 const SBI_CONSOLE_GETCHAR: usize = 2;
@@ -26,6 +44,10 @@ const SBI_REMOTE_SFENCE_VMA: usize = 6;
 const SBI_REMOTE_SFENCE_VMA_ASID: usize = 7;
 const SBI_SHUTDOWN: usize = 8;
 
+/// Hart State Management 扩展
+const SBI_EXT_HSM: usize = 0x48_534d;
+const SBI_HSM_HART_START: usize = 0;
+
 /// 向控制台输出一个字符
 ///
 /// 需要注意我们不能直接使用 Rust 中的 char 类型
@@ -50,3 +72,30 @@ pub fn shutdown() -> ! {
 pub fn set_timer(time: usize) {
     sbi_call(SBI_SET_TIMER, time, 0, 0);
 }
+
+/// 向 `hart_mask` 中的各个 hart 发送核间中断
+pub fn send_ipi(hart_mask: usize) {
+    sbi_call(SBI_SEND_IPI, &hart_mask as *const usize as usize, 0, 0);
+}
+
+/// 清除当前 hart 的核间中断
+pub fn clear_ipi() {
+    sbi_call(SBI_CLEAR_IPI, 0, 0, 0);
+}
+
+/// 让 `hart_mask` 中的各个 hart 刷新 `[start, start + size)` 区间的 TLB
+pub fn remote_sfence_vma(hart_mask: usize, start: usize, size: usize) {
+    sbi_call(
+        SBI_REMOTE_SFENCE_VMA,
+        &hart_mask as *const usize as usize,
+        start,
+        size,
+    );
+}
+
+/// 启动一个处于停止状态的 hart，它会在 S 态从物理地址 `start_address` 开始执行，`a1` 为 `opaque`
+///
+/// 返回 SBI 错误码，0 表示成功
+pub fn hart_start(hart_id: usize, start_address: usize, opaque: usize) -> isize {
+    sbi_call_ext(SBI_EXT_HSM, SBI_HSM_HART_START, hart_id, start_address, opaque)
+}
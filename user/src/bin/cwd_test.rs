@@ -0,0 +1,68 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::*;
+
+/// 测试中创建的目录
+const DIR: &str = "/cwd_test_dir";
+
+/// 检查当前工作目录
+fn assert_cwd(expected: &str) {
+    let mut buffer = [0u8; 64];
+    let length = sys_getcwd(&mut buffer);
+    assert_eq!(length, expected.len() as isize);
+    assert_eq!(&buffer[..length as usize], expected.as_bytes());
+}
+
+#[no_mangle]
+pub fn main() -> isize {
+    assert_cwd("/");
+    // 缓冲区放不下
+    assert_eq!(sys_getcwd(&mut [0u8; 0]), -1);
+
+    // 相对路径从工作目录开始查找
+    assert_eq!(sys_mkdir(DIR), 0);
+    assert_eq!(sys_chdir(&DIR[1..]), 0);
+    assert_cwd(DIR);
+    assert_eq!(sys_mkdir("sub"), 0);
+    assert_eq!(sys_chdir("./sub/"), 0);
+    assert_cwd("/cwd_test_dir/sub");
+    let fd = sys_open("../file.txt", O_WRONLY | O_CREAT);
+    assert!(fd >= 0, "failed to create file");
+    assert_eq!(sys_close(fd as usize), 0);
+    // 文件不是目录
+    assert_eq!(sys_chdir("../file.txt"), -1);
+    assert_eq!(sys_chdir("no_such_dir"), -1);
+    assert_cwd("/cwd_test_dir/sub");
+
+    // 根目录的上级仍是根目录，绝对路径不受工作目录影响
+    assert_eq!(sys_chdir("../../.."), 0);
+    assert_cwd("/");
+    assert_eq!(sys_chdir("/cwd_test_dir/sub"), 0);
+
+    // 子进程继承工作目录，但修改不影响父进程
+    let pid = sys_fork();
+    if pid == 0 {
+        assert_cwd("/cwd_test_dir/sub");
+        assert_eq!(sys_chdir(".."), 0);
+        assert_cwd(DIR);
+        sys_exit(0);
+    }
+    let mut exit_code = -1;
+    assert_eq!(sys_waitpid(pid, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+    assert_cwd("/cwd_test_dir/sub");
+
+    // 清理
+    assert_eq!(sys_chdir(".."), 0);
+    assert_eq!(sys_unlink("file.txt"), 0);
+    assert_eq!(sys_unlink("sub"), 0);
+    assert_eq!(sys_chdir("/"), 0);
+    assert_eq!(sys_unlink(DIR), 0);
+
+    println!("cwd_test passed");
+    0
+}
@@ -0,0 +1,67 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::*;
+
+/// 以 `flags` 打开设备
+fn open_device(path: &str, flags: usize) -> usize {
+    let fd = sys_open(path, flags);
+    assert!(fd >= 0, "failed to open {}", path);
+    fd as usize
+}
+
+/// 检查设备的类型
+fn assert_type(fd: usize, type_: u32) {
+    let mut stat = Stat::default();
+    assert_eq!(sys_fstat(fd, &mut stat), 0);
+    assert_eq!(stat.type_, type_);
+}
+
+#[no_mangle]
+pub fn main() -> isize {
+    let mut buffer = [0xffu8; 64];
+
+    // null：读到末尾，写入被丢弃
+    let fd = open_device("/dev/null", O_RDWR);
+    assert_type(fd, TYPE_CHAR_DEVICE);
+    assert_eq!(sys_read(fd, &mut buffer), 0);
+    assert_eq!(sys_write(fd, b"discarded"), 9);
+    assert_eq!(sys_close(fd), 0);
+
+    // zero：读到 0
+    let fd = open_device("/dev/zero", O_RDONLY);
+    assert_eq!(sys_read(fd, &mut buffer), 64);
+    assert!(buffer.iter().all(|&byte| byte == 0));
+    assert_eq!(sys_close(fd), 0);
+
+    // random：两次读到的内容不同
+    let fd = open_device("/dev/random", O_RDONLY);
+    let mut another = [0u8; 64];
+    assert_eq!(sys_read(fd, &mut buffer), 64);
+    assert_eq!(sys_read(fd, &mut another), 64);
+    assert_ne!(&buffer[..], &another[..]);
+    assert_eq!(sys_close(fd), 0);
+
+    // console：与标准输出相同
+    let fd = open_device("/dev/console", O_WRONLY);
+    assert_eq!(sys_write(fd, b"written to /dev/console\n"), 24);
+    assert_eq!(sys_close(fd), 0);
+
+    // 根文件系统所在的磁盘不能直接读写，交换区所在的磁盘不可见
+    let fd = open_device("/dev/disk0", O_RDWR);
+    assert_type(fd, TYPE_BLOCK_DEVICE);
+    assert_eq!(sys_read(fd, &mut buffer), -1);
+    assert_eq!(sys_write(fd, b"nope"), -1);
+    assert_eq!(sys_close(fd), 0);
+    assert_eq!(sys_open("/dev/disk1", O_RDONLY), -1);
+
+    // 设备文件系统中不能创建文件
+    assert_eq!(sys_open("/dev/new", O_WRONLY | O_CREAT), -1);
+    assert_eq!(sys_open("/dev/missing", O_RDONLY), -1);
+
+    println!("devfs_test passed");
+    0
+}
@@ -0,0 +1,82 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{config::FIXTURE, *};
+/// 子进程重定向后输出的内容
+const MESSAGE: &[u8] = b"printed by the child\n";
+
+#[no_mangle]
+pub fn main() -> isize {
+    // 复制的描述符共享读写位置
+    let fd = sys_open(FIXTURE, O_RDONLY);
+    assert!(fd >= 2, "failed to open {}", FIXTURE);
+    let fd = fd as usize;
+    let copy = sys_dup(fd);
+    assert!(copy >= 0 && copy as usize != fd, "dup failed");
+    let copy = copy as usize;
+    let mut buffer = [0u8; 64];
+    assert_eq!(sys_read(fd, &mut buffer[..6]), 6);
+    assert_eq!(sys_read(copy, &mut buffer[..4]), 4);
+    assert_eq!(&buffer[..4], b"from");
+    // 关闭其中一个，另一个仍然可用
+    assert_eq!(sys_close(fd), 0);
+    assert_eq!(sys_lseek(copy, 0, SEEK_SET), 0);
+    assert_eq!(sys_close(copy), 0);
+
+    // 不存在的描述符不能复制
+    assert_eq!(sys_dup(fd), -1);
+    assert_eq!(sys_dup2(fd, 5), -1);
+
+    // 描述符已满时，open、dup 和 pipe 都失败
+    let fd = sys_open(FIXTURE, O_RDONLY);
+    assert!(fd >= 2, "failed to open {}", FIXTURE);
+    let fd = fd as usize;
+    let mut last = fd;
+    while sys_dup(fd) >= 0 {
+        last += 1;
+    }
+    assert_eq!(sys_open(FIXTURE, O_RDONLY), -1);
+    // 只剩一个空闲描述符时，管道不能创建，也不会占用它
+    assert_eq!(sys_close(last), 0);
+    let mut pair = [0usize; 2];
+    assert_eq!(sys_pipe(&mut pair), -1);
+    assert_eq!(sys_dup(fd), last as isize);
+    for copy in fd..=last {
+        assert_eq!(sys_close(copy), 0);
+    }
+
+    // 子进程将标准输出重定向到管道，父进程从管道读出它的输出
+    let mut fds = [0usize; 2];
+    assert_eq!(sys_pipe(&mut fds), 0);
+    let pid = sys_fork();
+    assert!(pid >= 0, "fork failed");
+    if pid == 0 {
+        assert_eq!(sys_dup2(fds[1], STDOUT), STDOUT as isize);
+        assert_eq!(sys_close(fds[0]), 0);
+        assert_eq!(sys_close(fds[1]), 0);
+        print!("{}", core::str::from_utf8(MESSAGE).unwrap());
+        sys_exit(0);
+    }
+    assert_eq!(sys_close(fds[1]), 0);
+    let mut received = 0;
+    loop {
+        let size = sys_read(fds[0], &mut buffer[received..]);
+        assert!(size >= 0, "read failed");
+        if size == 0 {
+            break;
+        }
+        received += size as usize;
+    }
+    assert_eq!(&buffer[..received], MESSAGE);
+    let mut exit_code = -1;
+    assert_eq!(sys_waitpid(pid, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+    assert_eq!(sys_close(fds[0]), 0);
+
+    // 父进程的标准输出不受影响
+    println!("dup_test passed");
+    0
+}
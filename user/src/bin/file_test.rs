@@ -0,0 +1,66 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{config::FIXTURE, *};
+
+/// 测试文件的内容
+const FIXTURE_CONTENT: &[u8] = b"Hello from a fixture file!\nThe second line.\n";
+
+/// 测试中创建的文件
+const NEW_FILE: &str = "file_test.tmp";
+
+#[no_mangle]
+pub fn main() -> isize {
+    // 读取测试文件，分两次读取以检查读写位置
+    let fd = sys_open(FIXTURE, O_RDONLY);
+    assert!(fd >= 2, "failed to open {}", FIXTURE);
+    let fd = fd as usize;
+    let mut buffer = [0u8; 64];
+    assert_eq!(sys_read(fd, &mut buffer[..6]), 6);
+    let rest = sys_read(fd, &mut buffer[6..]);
+    assert_eq!(rest as usize, FIXTURE_CONTENT.len() - 6);
+    assert_eq!(&buffer[..FIXTURE_CONTENT.len()], FIXTURE_CONTENT);
+    // 已经读到末尾
+    assert_eq!(sys_read(fd, &mut buffer), 0);
+    // 回到开头再读
+    assert_eq!(sys_lseek(fd, 0, SEEK_SET), 0);
+    assert_eq!(sys_read(fd, &mut buffer[..5]), 5);
+    assert_eq!(&buffer[..5], b"Hello");
+    // 只读打开的文件不能写
+    assert_eq!(sys_write(fd, b"x"), -1);
+    assert_eq!(sys_close(fd), 0);
+    assert_eq!(sys_close(fd), -1);
+
+    // 不存在的文件
+    assert_eq!(sys_open("/no_such_file", O_RDONLY), -1);
+    // 控制台不能移动读写位置
+    assert_eq!(sys_lseek(STDOUT, 0, SEEK_SET), -1);
+
+    // 创建文件并写入
+    let fd = sys_open(NEW_FILE, O_RDWR | O_CREAT | O_TRUNC);
+    assert!(fd >= 2, "failed to create {}", NEW_FILE);
+    let fd = fd as usize;
+    assert_eq!(sys_write(fd, b"first "), 6);
+    assert_eq!(sys_write(fd, b"second"), 6);
+    assert_eq!(sys_lseek(fd, 0, SEEK_END), 12);
+    assert_eq!(sys_close(fd), 0);
+    // 文件已经存在时，O_EXCL 失败
+    assert_eq!(sys_open(NEW_FILE, O_RDWR | O_CREAT | O_EXCL), -1);
+    // 追加写入后重新读取
+    let fd = sys_open(NEW_FILE, O_WRONLY | O_APPEND) as usize;
+    assert_eq!(sys_write(fd, b"!"), 1);
+    assert_eq!(sys_close(fd), 0);
+    let fd = sys_open(NEW_FILE, O_RDONLY) as usize;
+    let size = sys_read(fd, &mut buffer) as usize;
+    assert_eq!(&buffer[..size], b"first second!");
+    // 关闭后描述符可以被复用
+    assert_eq!(sys_close(fd), 0);
+    assert_eq!(sys_open(NEW_FILE, O_RDONLY) as usize, fd);
+    assert_eq!(sys_close(fd), 0);
+
+    println!("file_test passed");
+    0
+}
@@ -0,0 +1,39 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{sys_exit, sys_fork, sys_getpid, sys_waitpid};
+
+/// 子进程的数量
+const CHILDREN: isize = 4;
+
+#[no_mangle]
+pub fn main() -> isize {
+    println!("fork_test: parent pid {}", sys_getpid());
+    for i in 0..CHILDREN {
+        let pid = sys_fork();
+        if pid == 0 {
+            // 子进程以各自不同的退出码结束
+            println!("child {} (pid {}) exiting", i, sys_getpid());
+            sys_exit(100 + i);
+        }
+        assert!(pid > 0, "fork failed");
+    }
+    // 回收所有子进程并检查退出码
+    let mut sum = 0;
+    for _ in 0..CHILDREN {
+        let mut exit_code = 0;
+        let pid = sys_waitpid(-1, &mut exit_code);
+        assert!(pid > 0, "waitpid failed");
+        println!("child pid {} exited with {}", pid, exit_code);
+        sum += exit_code;
+    }
+    // 已经没有子进程了
+    let mut exit_code = 0;
+    assert_eq!(sys_waitpid(-1, &mut exit_code), -1);
+    assert_eq!(sum, (100..100 + CHILDREN).sum::<isize>());
+    println!("fork_test passed");
+    0
+}
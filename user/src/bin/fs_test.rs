@@ -0,0 +1,75 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::*;
+
+/// 测试中创建的目录
+const DIR: &str = "/fs_test_dir";
+/// 目录中创建的文件
+const FILE: &str = "/fs_test_dir/a.txt";
+/// 文件改名后的路径
+const RENAMED: &str = "/fs_test_dir/b.txt";
+
+/// 在目录中查找名为 `name` 的目录项
+fn find_entry(dir: &str, name: &str) -> Option<DirEntry> {
+    let fd = sys_open(dir, O_RDONLY);
+    assert!(fd >= 0, "failed to open {}", dir);
+    let fd = fd as usize;
+    let mut entries = [DirEntry::default(); 4];
+    let mut found = None;
+    loop {
+        let count = sys_getdents(fd, &mut entries);
+        assert!(count >= 0, "getdents failed");
+        if count == 0 {
+            break;
+        }
+        if let Some(entry) = entries[..count as usize].iter().find(|e| e.name() == name) {
+            found = Some(*entry);
+        }
+    }
+    assert_eq!(sys_close(fd), 0);
+    found
+}
+
+#[no_mangle]
+pub fn main() -> isize {
+    // 创建目录，重复创建失败
+    assert_eq!(sys_mkdir(DIR), 0);
+    assert_eq!(sys_mkdir(DIR), -1);
+    assert!(find_entry("/", &DIR[1..]).unwrap().is_dir());
+
+    // 在目录中创建文件并检查文件信息
+    let fd = sys_open(FILE, O_WRONLY | O_CREAT);
+    assert!(fd >= 0, "failed to create {}", FILE);
+    let fd = fd as usize;
+    assert_eq!(sys_write(fd, b"0123456789"), 10);
+    let mut stat = Stat::default();
+    assert_eq!(sys_fstat(fd, &mut stat), 0);
+    assert_eq!(stat.type_, TYPE_FILE);
+    assert_eq!(stat.size, 10);
+    assert_eq!(sys_close(fd), 0);
+    let entry = find_entry(DIR, "a.txt").unwrap();
+    assert_eq!(entry.type_, TYPE_FILE);
+    assert_eq!(entry.inode, stat.inode);
+    // 控制台没有文件信息
+    assert_eq!(sys_fstat(STDOUT, &mut stat), -1);
+
+    // 改名
+    assert_eq!(sys_rename(FILE, RENAMED), 0);
+    assert!(find_entry(DIR, "a.txt").is_none());
+    assert!(find_entry(DIR, "b.txt").is_some());
+    assert_eq!(sys_open(FILE, O_RDONLY), -1);
+
+    // 非空目录不能删除
+    assert_eq!(sys_unlink(DIR), -1);
+    assert_eq!(sys_unlink(RENAMED), 0);
+    assert_eq!(sys_unlink(RENAMED), -1);
+    assert_eq!(sys_unlink(DIR), 0);
+    assert!(find_entry("/", &DIR[1..]).is_none());
+
+    println!("fs_test passed");
+    0
+}
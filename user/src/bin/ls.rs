@@ -0,0 +1,41 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::*;
+
+/// 每次读取的目录项个数
+const BATCH: usize = 8;
+
+/// 列出当前目录中的文件，目录名后加 `/`
+#[no_mangle]
+pub fn main() -> isize {
+    let fd = sys_open(".", O_RDONLY);
+    if fd < 0 {
+        println!("ls: cannot open current directory");
+        return -1;
+    }
+    let fd = fd as usize;
+    let mut entries = [DirEntry::default(); BATCH];
+    loop {
+        let count = sys_getdents(fd, &mut entries);
+        if count < 0 {
+            println!("ls: failed to read directory");
+            return -1;
+        }
+        if count == 0 {
+            break;
+        }
+        for entry in &entries[..count as usize] {
+            if entry.is_dir() {
+                println!("{}/", entry.name());
+            } else {
+                println!("{}", entry.name());
+            }
+        }
+    }
+    sys_close(fd);
+    0
+}
@@ -0,0 +1,20 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::console::getline;
+use user_lib::*;
+
+/// 从控制台读入路径并创建目录
+#[no_mangle]
+pub fn main() -> isize {
+    print!("mkdir: ");
+    let path = getline();
+    if sys_mkdir(&path) < 0 {
+        println!("mkdir: cannot create directory '{}'", path);
+        return -1;
+    }
+    0
+}
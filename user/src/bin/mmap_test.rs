@@ -0,0 +1,128 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use core::slice::from_raw_parts_mut;
+use user_lib::*;
+
+/// 测试中映射的文件
+const FILE: &str = "/tmp/mmap_test.txt";
+/// 文件的初始内容
+const CONTENT: &[u8] = b"memory mapped file";
+/// 大块匿名映射的长度
+const BIG_SIZE: usize = 1 << 20;
+
+/// 建立映射并转换为切片
+fn map(size: usize, prot: usize, flags: usize, fd: usize) -> &'static mut [u8] {
+    let address = sys_mmap(size, prot, flags, fd, 0);
+    assert!(address > 0, "mmap failed");
+    unsafe { from_raw_parts_mut(address as *mut u8, size) }
+}
+
+/// 打开 `FILE` 并读出全部内容，返回长度
+fn read_file(buffer: &mut [u8]) -> usize {
+    let fd = sys_open(FILE, O_RDONLY);
+    assert!(fd >= 0, "failed to open {}", FILE);
+    let size = sys_read(fd as usize, buffer);
+    assert!(size >= 0, "read failed");
+    assert_eq!(sys_close(fd as usize), 0);
+    size as usize
+}
+
+/// fork 一个子进程执行 `child`，并等待它正常退出
+fn in_child(child: impl FnOnce()) {
+    let pid = sys_fork();
+    if pid == 0 {
+        child();
+        sys_exit(0);
+    }
+    let mut exit_code = -1;
+    assert_eq!(sys_waitpid(pid, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+}
+
+#[no_mangle]
+pub fn main() -> isize {
+    let rw = PROT_READ | PROT_WRITE;
+
+    // 大块的匿名映射，初始为零
+    let big = map(BIG_SIZE, rw, MAP_PRIVATE | MAP_ANONYMOUS, 0);
+    assert!(big.iter().step_by(4096).all(|&byte| byte == 0));
+    for (i, byte) in big.iter_mut().enumerate().step_by(4096) {
+        *byte = i as u8 + 1;
+    }
+    // 私有映射在 fork 之后写时复制
+    in_child(|| big[0] = 0xff);
+    assert_eq!(big[0], 1);
+    assert_eq!(sys_munmap(big.as_ptr() as usize, BIG_SIZE), 0);
+    assert_eq!(sys_munmap(big.as_ptr() as usize, BIG_SIZE), -1);
+
+    // 共享的匿名映射在 fork 之后互相可见
+    let shared = map(4096, rw, MAP_SHARED | MAP_ANONYMOUS, 0);
+    in_child(|| shared[..5].copy_from_slice(b"child"));
+    assert_eq!(&shared[..5], b"child");
+    assert_eq!(sys_munmap(shared.as_ptr() as usize, 4096), 0);
+
+    // 参数不合法
+    assert_eq!(sys_mmap(0, rw, MAP_PRIVATE | MAP_ANONYMOUS, 0, 0), -1);
+    assert_eq!(sys_mmap(4096, rw, MAP_ANONYMOUS, 0, 0), -1);
+    assert_eq!(sys_mmap(4096, rw, MAP_SHARED | MAP_PRIVATE, 0, 0), -1);
+    // 超过物理内存和交换区能够容纳的长度
+    assert_eq!(sys_mmap(1 << 30, rw, MAP_PRIVATE | MAP_ANONYMOUS, 0, 0), -1);
+
+    // 准备文件
+    let fd = sys_open(FILE, O_RDWR | O_CREAT | O_TRUNC);
+    assert!(fd >= 0, "failed to create {}", FILE);
+    let fd = fd as usize;
+    assert_eq!(sys_write(fd, CONTENT), CONTENT.len() as isize);
+
+    // 私有的文件映射，超出文件的部分为零，修改不会写回
+    let private = map(4096, rw, MAP_PRIVATE, fd);
+    assert_eq!(&private[..CONTENT.len()], CONTENT);
+    assert!(private[CONTENT.len()..].iter().all(|&byte| byte == 0));
+    private[..6].copy_from_slice(b"MEMORY");
+    assert_eq!(sys_munmap(private.as_ptr() as usize, 4096), 0);
+    let mut buffer = [0u8; 64];
+    assert_eq!(read_file(&mut buffer), CONTENT.len());
+    assert_eq!(&buffer[..CONTENT.len()], CONTENT);
+
+    // 共享的文件映射，解除映射时写回，但不会超出文件的长度
+    let shared = map(4096, rw, MAP_SHARED, fd);
+    shared[..6].copy_from_slice(b"MEMORY");
+    shared[CONTENT.len()] = b'!';
+    assert_eq!(sys_munmap(shared.as_ptr() as usize, 4096), 0);
+    assert_eq!(read_file(&mut buffer), CONTENT.len());
+    assert_eq!(&buffer[..CONTENT.len()], b"MEMORY mapped file");
+    assert_eq!(sys_close(fd), 0);
+
+    // 只读打开的文件不能建立可写的共享映射
+    let fd = sys_open(FILE, O_RDONLY) as usize;
+    assert_eq!(sys_mmap(4096, rw, MAP_SHARED, fd, 0), -1);
+    assert_eq!(sys_close(fd), 0);
+    assert_eq!(sys_unlink(FILE), 0);
+
+    // 修改权限：只读之后子进程写入会被终止，恢复可写之后可以写入
+    let page = map(4096, rw, MAP_PRIVATE | MAP_ANONYMOUS, 0);
+    page[0] = 1;
+    let address = page.as_ptr() as usize;
+    assert_eq!(sys_mprotect(address, 4096, PROT_READ), 0);
+    assert_eq!(page[0], 1);
+    let pid = sys_fork();
+    if pid == 0 {
+        page[0] = 2;
+        sys_exit(0);
+    }
+    let mut exit_code = 0;
+    assert_eq!(sys_waitpid(pid, &mut exit_code), pid);
+    assert_ne!(exit_code, 0);
+    assert_eq!(sys_mprotect(address, 4096, rw), 0);
+    page[0] = 3;
+    assert_eq!(page[0], 3);
+    assert_eq!(sys_mprotect(address, 8192, PROT_READ), -1);
+    assert_eq!(sys_munmap(address, 4096), 0);
+
+    println!("mmap_test passed");
+    0
+}
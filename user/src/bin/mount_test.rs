@@ -0,0 +1,68 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    config::{FIXTURE, FIXTURE_DIR, FIXTURE_DISK},
+    *,
+};
+
+/// 检查当前工作目录
+fn assert_cwd(expected: &str) {
+    let mut buffer = [0u8; 64];
+    let length = sys_getcwd(&mut buffer);
+    assert_eq!(&buffer[..length as usize], expected.as_bytes());
+}
+
+#[no_mangle]
+pub fn main() -> isize {
+    // 根文件系统、交换区和不存在的设备都不能挂载
+    assert_eq!(sys_mount("disk0", FIXTURE_DIR), -1);
+    assert_eq!(sys_mount("disk1", FIXTURE_DIR), -1);
+    assert_eq!(sys_mount("disk9", FIXTURE_DIR), -1);
+    assert_eq!(sys_mount("nothing", FIXTURE_DIR), -1);
+    // 没有挂载的目录不能卸载
+    assert_eq!(sys_umount("/"), -1);
+    // 测试文件所在的磁盘启动时已经挂载，同一个磁盘不能挂载两次
+    assert_eq!(sys_mount(FIXTURE_DISK, "/"), -1);
+    assert_eq!(sys_mount(FIXTURE_DISK, FIXTURE_DIR), -1);
+
+    // 路径查找进入挂载的文件系统
+    let fd = sys_open(FIXTURE, O_RDONLY);
+    assert!(fd >= 0, "failed to open file on mounted disk");
+    let mut stat = Stat::default();
+    assert_eq!(sys_fstat(fd as usize, &mut stat), 0);
+    assert_eq!(stat.size, 44);
+    assert_eq!(sys_close(fd as usize), 0);
+
+    // 从挂载的文件系统的根目录中，`..` 回到挂载点的上级目录
+    assert_eq!(sys_chdir(FIXTURE_DIR), 0);
+    let fd = sys_open("hello.txt", O_RDONLY);
+    assert!(fd >= 0);
+    assert_eq!(sys_close(fd as usize), 0);
+    assert_eq!(sys_chdir(".."), 0);
+    assert_cwd("/");
+    let fd = sys_open("fixtures/../fixtures/hello.txt", O_RDONLY);
+    assert!(fd >= 0);
+    // 挂载点不能删除或移动
+    assert_eq!(sys_unlink(FIXTURE_DIR), -1);
+    assert_eq!(sys_rename(FIXTURE_DIR, "/moved"), -1);
+
+    // 还有打开的文件时不能卸载
+    assert_eq!(sys_umount(FIXTURE_DIR), -1);
+    assert_eq!(sys_close(fd as usize), 0);
+
+    // 卸载后回到原来的空目录
+    assert_eq!(sys_umount(FIXTURE_DIR), 0);
+    assert_eq!(sys_open(FIXTURE, O_RDONLY), -1);
+    // 重新挂载，供其他测试程序使用
+    assert_eq!(sys_mount(FIXTURE_DISK, FIXTURE_DIR), 0);
+    let fd = sys_open(FIXTURE, O_RDONLY);
+    assert!(fd >= 0, "failed to open file after remounting");
+    assert_eq!(sys_close(fd as usize), 0);
+
+    println!("mount_test passed");
+    0
+}
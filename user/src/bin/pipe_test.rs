@@ -0,0 +1,70 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::*;
+
+/// 生产者写入的总字节数，超过管道缓冲区以检查写入时的阻塞
+const TOTAL: usize = 0x4000;
+/// 每次写入的字节数
+const CHUNK: usize = 100;
+
+#[no_mangle]
+pub fn main() -> isize {
+    let mut fds = [0usize; 2];
+    assert_eq!(sys_pipe(&mut fds), 0);
+    let [read_fd, write_fd] = fds;
+
+    let pid = sys_fork();
+    assert!(pid >= 0, "fork failed");
+    if pid == 0 {
+        // 子进程作为生产者，关闭读端后写入 0, 1, 2, ... 循环的字节
+        assert_eq!(sys_close(read_fd), 0);
+        let mut buffer = [0u8; CHUNK];
+        let mut written = 0;
+        while written < TOTAL {
+            let size = CHUNK.min(TOTAL - written);
+            for (i, byte) in buffer[..size].iter_mut().enumerate() {
+                *byte = ((written + i) % 251) as u8;
+            }
+            assert_eq!(sys_write(write_fd, &buffer[..size]), size as isize);
+            written += size;
+        }
+        // 退出时关闭写端，消费者随后读到文件结束
+        sys_exit(0);
+    }
+
+    // 父进程作为消费者，必须关闭自己的写端，否则永远读不到文件结束
+    assert_eq!(sys_close(write_fd), 0);
+    // 写端只能写，读端只能读
+    assert_eq!(sys_write(read_fd, b"x"), -1);
+    let mut buffer = [0u8; 64];
+    let mut received = 0;
+    loop {
+        let size = sys_read(read_fd, &mut buffer);
+        assert!(size >= 0, "read failed");
+        if size == 0 {
+            break;
+        }
+        for (i, &byte) in buffer[..size as usize].iter().enumerate() {
+            assert_eq!(byte, ((received + i) % 251) as u8, "data corrupted");
+        }
+        received += size as usize;
+    }
+    assert_eq!(received, TOTAL);
+    let mut exit_code = -1;
+    assert_eq!(sys_waitpid(pid, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+    assert_eq!(sys_close(read_fd), 0);
+
+    // 读端全部关闭后，写入失败
+    assert_eq!(sys_pipe(&mut fds), 0);
+    assert_eq!(sys_close(fds[0]), 0);
+    assert_eq!(sys_write(fds[1], b"x"), -1);
+    assert_eq!(sys_close(fds[1]), 0);
+
+    println!("pipe_test passed");
+    0
+}
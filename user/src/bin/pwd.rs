@@ -0,0 +1,20 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::*;
+
+/// 打印当前工作目录
+#[no_mangle]
+pub fn main() -> isize {
+    let mut buffer = [0u8; 256];
+    let length = sys_getcwd(&mut buffer);
+    if length < 0 {
+        println!("pwd: failed to get current directory");
+        return -1;
+    }
+    println!("{}", core::str::from_utf8(&buffer[..length as usize]).unwrap());
+    0
+}
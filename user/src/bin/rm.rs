@@ -0,0 +1,20 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::console::getline;
+use user_lib::*;
+
+/// 从控制台读入路径并删除文件或空目录
+#[no_mangle]
+pub fn main() -> isize {
+    print!("rm: ");
+    let path = getline();
+    if sys_unlink(&path) < 0 {
+        println!("rm: cannot remove '{}'", path);
+        return -1;
+    }
+    0
+}
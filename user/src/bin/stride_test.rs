@@ -0,0 +1,43 @@
+//! 步幅调度的测试，内核需要启用 `stride` 特性（`cargo build --features stride`）
+
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{sys_exit, sys_fork, sys_getpid, sys_set_priority, sys_waitpid};
+
+/// 子进程的数量，第 i 个子进程的优先级为 i + 1
+const CHILDREN: isize = 4;
+
+/// 每个子进程需要完成的计算量
+const WORK: usize = 1 << 24;
+
+#[no_mangle]
+pub fn main() -> isize {
+    assert_eq!(sys_set_priority(0), -1);
+    for i in 0..CHILDREN {
+        let pid = sys_fork();
+        if pid == 0 {
+            // 完成相同的计算量，优先级越高的子进程应当越早结束
+            assert_eq!(sys_set_priority(i + 1), i + 1);
+            let mut sum = 0usize;
+            for j in 0..WORK {
+                sum = sum.wrapping_add(j);
+                unsafe { core::ptr::read_volatile(&sum) };
+            }
+            println!("priority {} (pid {}) finished", i + 1, sys_getpid());
+            sys_exit(i + 1);
+        }
+        assert!(pid > 0, "fork failed");
+    }
+    // 按结束顺序回收子进程
+    for _ in 0..CHILDREN {
+        let mut exit_code = 0;
+        assert!(sys_waitpid(-1, &mut exit_code) > 0, "waitpid failed");
+        println!("reaped child with priority {}", exit_code);
+    }
+    println!("stride_test finished");
+    0
+}
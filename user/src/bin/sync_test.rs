@@ -0,0 +1,40 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::*;
+
+/// 测试中写入的文件，保留在磁盘上，重新启动后可以检查内容
+const FILE: &str = "/sync_test.txt";
+/// 写入的块数，超过块缓存的容量以触发脏块的替换
+const BLOCKS: usize = 0x180;
+
+#[no_mangle]
+pub fn main() -> isize {
+    let fd = sys_open(FILE, O_RDWR | O_CREAT | O_TRUNC);
+    assert!(fd >= 0, "failed to create {}", FILE);
+    let fd = fd as usize;
+    let mut block = [0u8; 512];
+    for i in 0..BLOCKS {
+        block.iter_mut().for_each(|b| *b = (i % 256) as u8);
+        assert_eq!(sys_write(fd, &block), block.len() as isize);
+    }
+    assert_eq!(sys_fsync(fd), 0);
+
+    // 被替换出缓存的块也能正确读回
+    assert_eq!(sys_lseek(fd, 0, SEEK_SET), 0);
+    for i in 0..BLOCKS {
+        assert_eq!(sys_read(fd, &mut block), block.len() as isize);
+        assert!(block.iter().all(|&b| b == (i % 256) as u8), "block {} corrupted", i);
+    }
+    assert_eq!(sys_close(fd), 0);
+
+    // 控制台和管道不能 fsync
+    assert_eq!(sys_fsync(STDOUT), -1);
+    assert_eq!(sys_sync(), 0);
+
+    println!("sync_test passed");
+    0
+}
@@ -0,0 +1,89 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::*;
+
+/// 启动时挂载了 tmpfs 的目录
+const TMP: &str = "/tmp";
+/// 测试中创建的目录
+const DIR: &str = "/tmp/tmpfs_test";
+/// 目录中创建的文件
+const FILE: &str = "/tmp/tmpfs_test/a.txt";
+
+/// 打开 `path` 并读出全部内容，返回长度
+fn read_all(path: &str, buffer: &mut [u8]) -> usize {
+    let fd = sys_open(path, O_RDONLY);
+    assert!(fd >= 0, "failed to open {}", path);
+    let size = sys_read(fd as usize, buffer);
+    assert!(size >= 0, "read failed");
+    assert_eq!(sys_close(fd as usize), 0);
+    size as usize
+}
+
+#[no_mangle]
+pub fn main() -> isize {
+    // 创建目录和文件
+    assert_eq!(sys_mkdir(DIR), 0);
+    let fd = sys_open(FILE, O_RDWR | O_CREAT);
+    assert!(fd >= 0, "failed to create {}", FILE);
+    let fd = fd as usize;
+    assert_eq!(sys_write(fd, b"hello tmpfs"), 11);
+    // 在文件末尾之后写入，中间填充 0
+    assert_eq!(sys_lseek(fd, 16, SEEK_SET), 16);
+    assert_eq!(sys_write(fd, b"!"), 1);
+    let mut stat = Stat::default();
+    assert_eq!(sys_fstat(fd, &mut stat), 0);
+    assert_eq!(stat.type_, TYPE_FILE);
+    assert_eq!(stat.size, 17);
+    assert_eq!(sys_close(fd), 0);
+
+    let mut buffer = [0xffu8; 32];
+    assert_eq!(read_all(FILE, &mut buffer), 17);
+    assert_eq!(&buffer[..11], b"hello tmpfs");
+    assert!(buffer[11..16].iter().all(|&byte| byte == 0));
+
+    // 截断
+    let fd = sys_open(FILE, O_WRONLY | O_TRUNC);
+    assert!(fd >= 0, "failed to truncate {}", FILE);
+    assert_eq!(sys_write(fd as usize, b"short"), 5);
+    assert_eq!(sys_close(fd as usize), 0);
+    assert_eq!(read_all(FILE, &mut buffer), 5);
+    assert_eq!(&buffer[..5], b"short");
+
+    // 改名、跨目录移动，目录不能移动到自己之中
+    assert_eq!(sys_rename(FILE, "/tmp/tmpfs_test/b.txt"), 0);
+    assert_eq!(sys_open(FILE, O_RDONLY), -1);
+    assert_eq!(sys_rename("/tmp/tmpfs_test/b.txt", "/tmp/b.txt"), 0);
+    assert_eq!(read_all("/tmp/b.txt", &mut buffer), 5);
+    assert_eq!(sys_rename(DIR, "/tmp/tmpfs_test/inner"), -1);
+    // 不能移动到其他文件系统
+    assert_eq!(sys_rename("/tmp/b.txt", "/b.txt"), -1);
+
+    // 非空目录不能删除
+    assert_eq!(sys_rename("/tmp/b.txt", "/tmp/tmpfs_test/b.txt"), 0);
+    assert_eq!(sys_unlink(DIR), -1);
+    assert_eq!(sys_unlink("/tmp/tmpfs_test/b.txt"), 0);
+    assert_eq!(sys_unlink(DIR), 0);
+
+    // 在 tmpfs 中切换工作目录，`..` 回到挂载点的上级目录
+    assert_eq!(sys_chdir(TMP), 0);
+    assert_eq!(sys_mkdir("sub"), 0);
+    assert_eq!(sys_chdir("sub/../.."), 0);
+    assert_eq!(sys_unlink("/tmp/sub"), 0);
+
+    // 再挂载一个 tmpfs 会遮住原来的内容，卸载后恢复
+    assert_eq!(sys_mkdir("/tmp/kept"), 0);
+    assert_eq!(sys_mount("tmpfs", TMP), 0);
+    assert_eq!(sys_open("/tmp/kept", O_RDONLY), -1);
+    assert_eq!(sys_umount(TMP), 0);
+    let fd = sys_open("/tmp/kept", O_RDONLY);
+    assert!(fd >= 0, "tmpfs content lost after umount");
+    assert_eq!(sys_close(fd as usize), 0);
+    assert_eq!(sys_unlink("/tmp/kept"), 0);
+
+    println!("tmpfs_test passed");
+    0
+}
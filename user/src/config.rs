@@ -1,2 +1,9 @@
 /// 每个用户进程所用的堆大小（1M）
 pub const USER_HEAP_SIZE: usize = 0x10_0000;
+
+/// 测试文件所在的磁盘（见 `user/fixtures`），启动时由内核挂载到 [`FIXTURE_DIR`]
+pub const FIXTURE_DISK: &str = "disk2";
+/// 测试文件所在磁盘的挂载点
+pub const FIXTURE_DIR: &str = "/fixtures";
+/// 测试文件
+pub const FIXTURE: &str = "/fixtures/hello.txt";
@@ -56,3 +56,19 @@ pub fn getchars() -> String {
         }
     }
 }
+
+/// 从控制台读取一行（阻塞），输入的字符会回显，返回的字符串不含换行
+pub fn getline() -> String {
+    let mut line = String::new();
+    loop {
+        let string = getchars();
+        for c in string.chars() {
+            if c == '\n' || c == '\r' {
+                print!("\n");
+                return line;
+            }
+            print!("{}", c);
+            line.push(c);
+        }
+    }
+}
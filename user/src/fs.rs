@@ -0,0 +1,80 @@
+//! 文件信息 [`Stat`] 和目录项 [`DirEntry`]
+//!
+//! 内存布局与 `os` crate 中 `fs/stat.rs` 的定义相同，修改时需要同步
+
+/// 目录项中文件名的最大长度
+pub const NAME_MAX: usize = 256;
+
+/// 普通文件
+pub const TYPE_FILE: u32 = 1;
+/// 目录
+pub const TYPE_DIR: u32 = 2;
+/// 符号链接
+pub const TYPE_SYMLINK: u32 = 3;
+/// 字符设备
+pub const TYPE_CHAR_DEVICE: u32 = 4;
+/// 块设备
+pub const TYPE_BLOCK_DEVICE: u32 = 5;
+/// 命名管道
+pub const TYPE_NAMED_PIPE: u32 = 6;
+/// 套接字
+pub const TYPE_SOCKET: u32 = 7;
+
+/// 文件信息，由 [`sys_fstat`](crate::sys_fstat) 返回
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Stat {
+    /// 所在设备的编号
+    pub dev: u64,
+    /// INode 编号
+    pub inode: u64,
+    /// 文件类型，为 `TYPE_` 开头的常量
+    pub type_: u32,
+    /// 权限
+    pub mode: u32,
+    /// 硬链接数
+    pub nlinks: u64,
+    /// 文件大小（字节）
+    pub size: u64,
+    /// 块大小
+    pub blk_size: u64,
+    /// 占用的块数
+    pub blocks: u64,
+}
+
+/// 目录项，由 [`sys_getdents`](crate::sys_getdents) 返回
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct DirEntry {
+    /// INode 编号
+    pub inode: u64,
+    /// 文件类型，为 `TYPE_` 开头的常量
+    pub type_: u32,
+    /// 文件名的长度
+    pub name_len: u32,
+    /// 文件名，只有前 `name_len` 个字节有效
+    pub name: [u8; NAME_MAX],
+}
+
+impl Default for DirEntry {
+    fn default() -> Self {
+        Self {
+            inode: 0,
+            type_: 0,
+            name_len: 0,
+            name: [0; NAME_MAX],
+        }
+    }
+}
+
+impl DirEntry {
+    /// 文件名
+    pub fn name(&self) -> &str {
+        core::str::from_utf8(&self.name[..self.name_len as usize]).unwrap_or("?")
+    }
+
+    /// 是否为目录
+    pub fn is_dir(&self) -> bool {
+        self.type_ == TYPE_DIR
+    }
+}
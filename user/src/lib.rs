@@ -10,6 +10,7 @@
 #![feature(linkage)]
 
 pub mod config;
+pub mod fs;
 pub mod syscall;
 
 #[macro_use]
@@ -17,6 +18,7 @@ pub mod console;
 
 extern crate alloc;
 
+pub use crate::fs::*;
 pub use crate::syscall::*;
 use buddy_system_allocator::LockedHeap;
 use config::USER_HEAP_SIZE;
@@ -1,39 +1,222 @@
 //! 系统调用
 
+use crate::fs::{DirEntry, Stat};
+
 pub const STDIN: usize = 0;
 pub const STDOUT: usize = 1;
 
+/// [`sys_open`] 只读
+pub const O_RDONLY: usize = 1 << 0;
+/// [`sys_open`] 只写
+pub const O_WRONLY: usize = 1 << 1;
+/// [`sys_open`] 读写
+pub const O_RDWR: usize = O_RDONLY | O_WRONLY;
+/// [`sys_open`] 每次写入到文件末尾
+pub const O_APPEND: usize = 1 << 2;
+/// [`sys_open`] 执行 exec 时关闭
+pub const O_CLOEXEC: usize = 1 << 3;
+/// [`sys_open`] 文件不存在时创建
+pub const O_CREAT: usize = 1 << 4;
+/// [`sys_open`] 打开时将文件截断为空
+pub const O_TRUNC: usize = 1 << 5;
+/// [`sys_open`] 与 [`O_CREAT`] 一同使用，文件已经存在时失败
+pub const O_EXCL: usize = 1 << 6;
+
+/// [`sys_mmap`] 映射可读
+pub const PROT_READ: usize = 1 << 0;
+/// [`sys_mmap`] 映射可写
+pub const PROT_WRITE: usize = 1 << 1;
+/// [`sys_mmap`] 映射可执行
+pub const PROT_EXEC: usize = 1 << 2;
+/// [`sys_mmap`] 共享映射，fork 之后父子进程共享，文件映射的修改会写回文件
+pub const MAP_SHARED: usize = 1 << 0;
+/// [`sys_mmap`] 私有映射，fork 之后写时复制，文件映射的修改不会写回文件
+pub const MAP_PRIVATE: usize = 1 << 1;
+/// [`sys_mmap`] 匿名映射，内容初始为零
+pub const MAP_ANONYMOUS: usize = 1 << 5;
+
+/// [`sys_lseek`] 从文件开头计算位置
+pub const SEEK_SET: usize = 0;
+/// [`sys_lseek`] 从当前位置计算位置
+pub const SEEK_CUR: usize = 1;
+/// [`sys_lseek`] 从文件末尾计算位置
+pub const SEEK_END: usize = 2;
+
+const SYSCALL_GETCWD: usize = 17;
+const SYSCALL_DUP: usize = 23;
+const SYSCALL_DUP2: usize = 24;
+const SYSCALL_MKDIR: usize = 34;
+const SYSCALL_UNLINK: usize = 35;
+const SYSCALL_RENAME: usize = 38;
+const SYSCALL_UMOUNT: usize = 39;
+const SYSCALL_MOUNT: usize = 40;
+const SYSCALL_CHDIR: usize = 49;
+const SYSCALL_OPEN: usize = 56;
+const SYSCALL_CLOSE: usize = 57;
+const SYSCALL_PIPE: usize = 59;
+const SYSCALL_GETDENTS: usize = 61;
+const SYSCALL_LSEEK: usize = 62;
 const SYSCALL_READ: usize = 63;
 const SYSCALL_WRITE: usize = 64;
+const SYSCALL_FSTAT: usize = 80;
+const SYSCALL_SYNC: usize = 81;
+const SYSCALL_FSYNC: usize = 82;
 const SYSCALL_EXIT: usize = 93;
+const SYSCALL_SET_PRIORITY: usize = 140;
+const SYSCALL_GETPID: usize = 172;
+const SYSCALL_MUNMAP: usize = 215;
+const SYSCALL_FORK: usize = 220;
+const SYSCALL_EXEC: usize = 221;
+const SYSCALL_MMAP: usize = 222;
+const SYSCALL_MPROTECT: usize = 226;
+const SYSCALL_WAITPID: usize = 260;
 
 /// 将参数放在对应寄存器中，并执行 `ecall`
 fn syscall(id: usize, arg0: usize, arg1: usize, arg2: usize) -> isize {
+    syscall4(id, arg0, arg1, arg2, 0)
+}
+
+/// 与 [`syscall`] 相同，但多一个参数
+fn syscall4(id: usize, arg0: usize, arg1: usize, arg2: usize, arg3: usize) -> isize {
+    syscall6(id, [arg0, arg1, arg2, arg3, 0, 0])
+}
+
+/// 与 [`syscall`] 相同，但有六个参数
+fn syscall6(id: usize, args: [usize; 6]) -> isize {
     // 返回值
     let mut ret;
     unsafe {
         llvm_asm!("ecall"
             : "={x10}" (ret)
-            : "{x10}" (arg0), "{x11}" (arg1), "{x12}" (arg2), "{x17}" (id)
+            : "{x10}" (args[0]), "{x11}" (args[1]), "{x12}" (args[2]), "{x13}" (args[3]),
+              "{x14}" (args[4]), "{x15}" (args[5]), "{x17}" (id)
             : "memory"      // 如果汇编可能改变内存，则需要加入 memory 选项
             : "volatile"); // 防止编译器做激进的优化（如调换指令顺序等破坏 SBI 调用行为的优化）
     }
     ret
 }
 
+/// 打开 `path` 处的文件，`flags` 为 `O_` 开头的各个选项
+///
+/// 返回文件描述符，失败时返回 -1
+pub fn sys_open(path: &str, flags: usize) -> isize {
+    syscall(SYSCALL_OPEN, path.as_ptr() as usize, path.len(), flags)
+}
+
+/// 切换当前工作目录，成功时返回 0，失败时返回 -1
+pub fn sys_chdir(path: &str) -> isize {
+    syscall(SYSCALL_CHDIR, path.as_ptr() as usize, path.len(), 0)
+}
+
+/// 将当前工作目录的绝对路径写入 `buffer`
+///
+/// 返回路径的长度，`buffer` 放不下时返回 -1
+pub fn sys_getcwd(buffer: &mut [u8]) -> isize {
+    syscall(SYSCALL_GETCWD, buffer.as_mut_ptr() as usize, buffer.len(), 0)
+}
+
+/// 创建目录，成功时返回 0，失败时返回 -1
+pub fn sys_mkdir(path: &str) -> isize {
+    syscall(SYSCALL_MKDIR, path.as_ptr() as usize, path.len(), 0)
+}
+
+/// 删除文件或空目录，成功时返回 0，失败时返回 -1
+pub fn sys_unlink(path: &str) -> isize {
+    syscall(SYSCALL_UNLINK, path.as_ptr() as usize, path.len(), 0)
+}
+
+/// 将 `old_path` 处的文件移动到 `new_path`，成功时返回 0，失败时返回 -1
+pub fn sys_rename(old_path: &str, new_path: &str) -> isize {
+    syscall4(
+        SYSCALL_RENAME,
+        old_path.as_ptr() as usize,
+        old_path.len(),
+        new_path.as_ptr() as usize,
+        new_path.len(),
+    )
+}
+
+/// 将 `source` 对应的文件系统挂载到目录 `target` 上，成功时返回 0，失败时返回 -1
+///
+/// `source` 为 `diskN` 时表示第 N 个块设备（从 0 开始，前两个分别为根文件系统和交换区）
+pub fn sys_mount(source: &str, target: &str) -> isize {
+    syscall4(
+        SYSCALL_MOUNT,
+        source.as_ptr() as usize,
+        source.len(),
+        target.as_ptr() as usize,
+        target.len(),
+    )
+}
+
+/// 卸载挂载在 `target` 上的文件系统，成功时返回 0，失败时返回 -1
+pub fn sys_umount(target: &str) -> isize {
+    syscall(SYSCALL_UMOUNT, target.as_ptr() as usize, target.len(), 0)
+}
+
+/// 获取文件信息，成功时返回 0，失败时返回 -1（例如控制台和管道没有文件信息）
+pub fn sys_fstat(fd: usize, stat: &mut Stat) -> isize {
+    syscall(SYSCALL_FSTAT, fd, stat as *mut Stat as usize, 0)
+}
+
+/// 从目录的当前位置读取目录项
+///
+/// 返回读取的个数，已经读完时返回 0，失败时返回 -1
+pub fn sys_getdents(fd: usize, entries: &mut [DirEntry]) -> isize {
+    syscall(
+        SYSCALL_GETDENTS,
+        fd,
+        entries.as_mut_ptr() as usize,
+        entries.len(),
+    )
+}
+
+/// 将文件系统的所有修改写回磁盘，成功时返回 0，失败时返回 -1
+pub fn sys_sync() -> isize {
+    syscall(SYSCALL_SYNC, 0, 0, 0)
+}
+
+/// 将文件的修改写回磁盘，成功时返回 0，失败时返回 -1（例如控制台和管道）
+pub fn sys_fsync(fd: usize) -> isize {
+    syscall(SYSCALL_FSYNC, fd, 0, 0)
+}
+
+/// 关闭文件描述符，成功时返回 0，失败时返回 -1
+pub fn sys_close(fd: usize) -> isize {
+    syscall(SYSCALL_CLOSE, fd, 0, 0)
+}
+
+/// 复制文件描述符，返回编号最小的空闲描述符，失败时返回 -1
+///
+/// 两个描述符共享读写位置
+pub fn sys_dup(fd: usize) -> isize {
+    syscall(SYSCALL_DUP, fd, 0, 0)
+}
+
+/// 将文件描述符 `fd` 复制到 `new_fd`，`new_fd` 原先打开的文件会被关闭
+///
+/// 返回 `new_fd`，失败时返回 -1
+pub fn sys_dup2(fd: usize, new_fd: usize) -> isize {
+    syscall(SYSCALL_DUP2, fd, new_fd, 0)
+}
+
+/// 创建一个管道，读端和写端的描述符依次写入 `fds`
+///
+/// 成功时返回 0。所有写端关闭后，从读端读取完剩余数据会返回 0
+pub fn sys_pipe(fds: &mut [usize; 2]) -> isize {
+    syscall(SYSCALL_PIPE, fds.as_mut_ptr() as usize, 0, 0)
+}
+
 /// 读取字符
+///
+/// 暂无输入时会阻塞，直到读到数据
 pub fn sys_read(fd: usize, buffer: &mut [u8]) -> isize {
-    loop {
-        let ret = syscall(
-            SYSCALL_READ,
-            fd,
-            buffer as *const [u8] as *const u8 as usize,
-            buffer.len(),
-        );
-        if ret > 0 {
-            return ret;
-        }
-    }
+    syscall(
+        SYSCALL_READ,
+        fd,
+        buffer as *const [u8] as *const u8 as usize,
+        buffer.len(),
+    )
 }
 
 /// 打印字符串
@@ -46,8 +229,77 @@ pub fn sys_write(fd: usize, buffer: &[u8]) -> isize {
     )
 }
 
+/// 移动文件的读写位置，`whence` 为 [`SEEK_SET`]、[`SEEK_CUR`] 或 [`SEEK_END`]
+///
+/// 返回新的位置，文件不支持移动或位置不合法时返回 -1
+pub fn sys_lseek(fd: usize, offset: isize, whence: usize) -> isize {
+    syscall(SYSCALL_LSEEK, fd, offset as usize, whence)
+}
+
 /// 退出并返回数值
 pub fn sys_exit(code: isize) -> ! {
     syscall(SYSCALL_EXIT, code as usize, 0, 0);
     unreachable!()
 }
+
+/// 设置当前线程的调度优先级，越大则分到的时间片越多
+///
+/// 只有启用 `stride` 特性、使用步幅调度时优先级才起作用，默认的多级反馈队列调度会忽略它。
+/// 成功时返回设置的优先级，优先级不是正数时返回 -1
+pub fn sys_set_priority(priority: isize) -> isize {
+    syscall(SYSCALL_SET_PRIORITY, priority as usize, 0, 0)
+}
+
+/// 复制当前进程
+///
+/// 父进程中返回子进程的 ID，子进程中返回 0
+pub fn sys_fork() -> isize {
+    syscall(SYSCALL_FORK, 0, 0, 0)
+}
+
+/// 将当前进程替换为文件系统中 `path` 处的程序
+///
+/// 成功时不会返回，失败时返回 -1
+pub fn sys_exec(path: &str) -> isize {
+    syscall(SYSCALL_EXEC, path.as_ptr() as usize, path.len(), 0)
+}
+
+/// 获取当前进程的 ID
+pub fn sys_getpid() -> isize {
+    syscall(SYSCALL_GETPID, 0, 0, 0)
+}
+
+/// 等待 ID 为 `pid` 的子进程退出（`pid` 为 -1 时等待任一子进程），并将退出码写入 `exit_code`
+///
+/// 返回回收的子进程 ID，没有符合的子进程时返回 -1
+pub fn sys_waitpid(pid: isize, exit_code: &mut isize) -> isize {
+    syscall(
+        SYSCALL_WAITPID,
+        pid as usize,
+        exit_code as *mut isize as usize,
+        0,
+    )
+}
+
+/// 建立一段长度为 `size` 的映射，返回其起始地址，失败时返回 -1
+///
+/// `prot` 为 `PROT_` 开头的权限；`flags` 为 [`MAP_SHARED`] 或 [`MAP_PRIVATE`]，可以再加上 [`MAP_ANONYMOUS`]。
+/// 不是匿名映射时，映射文件 `fd` 中从 `offset`（按页对齐）开始的内容
+pub fn sys_mmap(size: usize, prot: usize, flags: usize, fd: usize, offset: usize) -> isize {
+    // 映射的地址由内核选择
+    syscall6(SYSCALL_MMAP, [0, size, prot, flags, fd, offset])
+}
+
+/// 解除 [`sys_mmap`] 建立的映射，成功时返回 0，失败时返回 -1
+///
+/// `address` 和 `size` 必须恰好是一段映射，共享的文件映射会写回文件
+pub fn sys_munmap(address: usize, size: usize) -> isize {
+    syscall(SYSCALL_MUNMAP, address, size, 0)
+}
+
+/// 修改一段映射的权限，成功时返回 0，失败时返回 -1
+///
+/// `address` 和 `size` 必须恰好是一段映射
+pub fn sys_mprotect(address: usize, size: usize, prot: usize) -> isize {
+    syscall(SYSCALL_MPROTECT, address, size, prot)
+}